@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+use crate::display::Display;
+use crate::framebuffer::MxcfbRect;
+use crate::util::Point;
+
+// frames per measurement, enough to even out the first touch of the framebuffer mapping
+const ROUNDS: u32 = 10;
+
+fn time<F: FnMut(u32)>(mut f: F) -> Duration {
+    let start = Instant::now();
+    for round in 0..ROUNDS {
+        f(round);
+    }
+    start.elapsed() / ROUNDS
+}
+
+/* Writes whole frames to `display` pixel by pixel and in bulk, and prints how long a frame takes
+ * each way. no update is sent, so on the Kobo nothing shows up on the panel.
+ */
+pub fn run<D: Display>(display: &mut D) {
+    let width = display.width();
+    let height = display.height();
+    let rect = MxcfbRect {
+        top: 0,
+        left: 0,
+        width,
+        height,
+    };
+    // a gradient, so no backend can get away with writing the same value over and over
+    let pixels: Vec<u8> = (0..width * height)
+        .map(|i| ((i % width + i / width) % 256) as u8)
+        .collect();
+
+    let per_pixel = time(|round| {
+        for y in 0..height {
+            for x in 0..width {
+                let g = pixels[(y * width + x) as usize].wrapping_add(round as u8);
+                display.set_pixel(Point::new(x, y), [g, g, g]);
+            }
+        }
+    });
+    let blit = time(|_| display.blit_gray(&rect, &pixels, width as usize));
+    let fill = time(|round| display.fill_rect(&rect, round as u8 * 25));
+
+    println!("{}x{}, average of {} frames", width, height, ROUNDS);
+    println!("set_pixel   {:8.2} ms", per_pixel.as_secs_f64() * 1000.0);
+    println!(
+        "blit_gray   {:8.2} ms  ({:.1}x)",
+        blit.as_secs_f64() * 1000.0,
+        per_pixel.as_secs_f64() / blit.as_secs_f64()
+    );
+    println!(
+        "fill_rect   {:8.2} ms  ({:.1}x)",
+        fill.as_secs_f64() * 1000.0,
+        per_pixel.as_secs_f64() / fill.as_secs_f64()
+    );
+}
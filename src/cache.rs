@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::openweather::WeatherReport;
+use crate::util::unix_time;
+
+/* The last weather report we got, kept on disk so there is something to show while Wi-Fi is off.
+ * The report is stored as a JSON string together with a checksum of exactly that string, so a
+ * file that was cut short by a crash or power loss is noticed and thrown away.
+ */
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    // unix time of the fetch
+    fetched_at: i64,
+    checksum: String,
+    report: String,
+}
+
+/* a report together with its age, either fresh from the network or loaded from the cache */
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub report: WeatherReport,
+    pub fetched_at: i64,
+    // the network failed and this is the cached report
+    pub stale: bool,
+}
+
+impl Snapshot {
+    pub fn fresh(report: WeatherReport) -> Snapshot {
+        Snapshot {
+            report,
+            fetched_at: unix_time(),
+            stale: false,
+        }
+    }
+}
+
+// the fetch time alone is no reason to redraw, it's only shown for stale reports
+impl PartialEq for Snapshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.report == other.report
+            && self.stale == other.stale
+            && (!self.stale || self.fetched_at == other.fetched_at)
+    }
+}
+
+// FNV-1a, stable across Rust versions unlike std's DefaultHasher
+fn checksum(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub struct Cache {
+    path: PathBuf,
+}
+
+impl Cache {
+    pub fn new<P: AsRef<Path>>(path: P) -> Cache {
+        Cache {
+            path: path.as_ref().to_owned(),
+        }
+    }
+
+    pub fn store(&self, snapshot: &Snapshot) -> io::Result<()> {
+        let report = serde_json::to_string(&snapshot.report)?;
+        let file = CacheFile {
+            fetched_at: snapshot.fetched_at,
+            checksum: checksum(report.as_bytes()),
+            report,
+        };
+
+        // write next to the cache and rename, so we never leave a half written cache behind
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+
+    /* the cached report marked as stale. a missing cache is normal on the first run, anything
+     * else that's wrong with the file is reported and the cache ignored.
+     */
+    pub fn load(&self) -> Option<Snapshot> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                eprintln!("cannot read cache {}: {}", self.path.display(), e);
+                return None;
+            }
+        };
+
+        let file: CacheFile = match serde_json::from_slice(&data) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("ignoring corrupt cache {}: {}", self.path.display(), e);
+                return None;
+            }
+        };
+        if checksum(file.report.as_bytes()) != file.checksum {
+            eprintln!(
+                "ignoring corrupt cache {}: checksum mismatch",
+                self.path.display()
+            );
+            return None;
+        }
+
+        match serde_json::from_str(&file.report) {
+            Ok(report) => Some(Snapshot {
+                report,
+                fetched_at: file.fetched_at,
+                stale: true,
+            }),
+            Err(e) => {
+                eprintln!("ignoring outdated cache {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: [&str; 4] = [
+        include_str!("../fixtures/weather_new_york.json"),
+        include_str!("../fixtures/weather_pacific.json"),
+        include_str!("../fixtures/weather_tromso.json"),
+        include_str!("../fixtures/weather_kolkata.json"),
+    ];
+    const FORECAST: &str = include_str!("../fixtures/forecast_new_york.json");
+
+    fn snapshot(json: &str) -> Snapshot {
+        Snapshot {
+            report: WeatherReport {
+                current: serde_json::from_str(json).unwrap(),
+                forecast: serde_json::from_str(FORECAST).unwrap(),
+            },
+            fetched_at: 1728921060,
+            stale: false,
+        }
+    }
+
+    // a cache in a directory of its own, so tests running at the same time don't share files
+    fn cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("kobors-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Cache::new(dir.join("weather.json"))
+    }
+
+    #[test]
+    fn loads_what_was_stored() {
+        let cache = cache("stored");
+        // the cache stores reports with our own Serialize, which must read back the same
+        for json in FIXTURES {
+            let snapshot = snapshot(json);
+            cache.store(&snapshot).unwrap();
+            assert!(!cache.path.with_extension("tmp").exists());
+
+            let loaded = cache.load().unwrap();
+            assert_eq!(loaded.report, snapshot.report);
+            assert_eq!(loaded.fetched_at, snapshot.fetched_at);
+            assert!(loaded.stale);
+        }
+        std::fs::remove_dir_all(cache.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn ignores_corrupt_caches() {
+        let cache = cache("corrupt");
+        assert!(cache.load().is_none());
+
+        cache.store(&snapshot(FIXTURES[0])).unwrap();
+        let data = std::fs::read(&cache.path).unwrap();
+
+        // still valid JSON, but not what the checksum was made of
+        let mut flipped = data.clone();
+        let i = flipped.windows(4).position(|w| w == b"York").unwrap();
+        flipped[i] ^= 0x20;
+        std::fs::write(&cache.path, &flipped).unwrap();
+        assert!(cache.load().is_none());
+
+        std::fs::write(&cache.path, &data[..data.len() / 2]).unwrap();
+        assert!(cache.load().is_none());
+
+        std::fs::write(&cache.path, &data).unwrap();
+        assert!(cache.load().is_some());
+        std::fs::remove_dir_all(cache.path.parent().unwrap()).unwrap();
+        assert!(cache.load().is_none());
+    }
+}
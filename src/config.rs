@@ -0,0 +1,576 @@
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::dither::Dither;
+use crate::fonts::DEFAULT_FONT;
+use crate::framebuffer::{self, Mode, Model};
+use crate::http::Url;
+use crate::localtime::{self, LocalTime};
+use crate::screen::{self, Rotation};
+use crate::util::FontSetting;
+
+// used when neither --config nor KOBORS_CONFIG is given
+pub const DEFAULT_PATH: &str = "kobors.toml";
+pub const ENV_VAR: &str = "KOBORS_CONFIG";
+// seconds, see refresh_interval
+const MAX_REFRESH_INTERVAL: u64 = 24 * 60 * 60;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "cannot read config {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => {
+                write!(f, "cannot parse config {}: {}", path.display(), e)
+            }
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    Standard,
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    // value of the `units` query parameter of the OpenWeather API
+    pub fn as_query(&self) -> &'static str {
+        match self {
+            Units::Standard => "standard",
+            Units::Metric => "metric",
+            Units::Imperial => "imperial",
+        }
+    }
+
+    pub fn temperature(&self) -> &'static str {
+        match self {
+            Units::Standard => "K",
+            Units::Metric => "°C",
+            Units::Imperial => "°F",
+        }
+    }
+
+    // wind speed, the API has no km/h
+    pub fn speed(&self) -> &'static str {
+        match self {
+            Units::Standard | Units::Metric => "m/s",
+            Units::Imperial => "mph",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Location {
+    Coordinates { lat: f32, lon: f32 },
+    City(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fonts {
+    #[serde(default = "default_font_big")]
+    pub big: FontSetting,
+    #[serde(default = "default_font_med")]
+    pub med: FontSetting,
+    #[serde(default = "default_font_small")]
+    pub small: FontSetting,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(default = "default_margin")]
+    pub margin: u32,
+    #[serde(default = "default_icons")]
+    pub icons: PathBuf,
+    // directories searched for the font files before the one next to the executable
+    #[serde(default)]
+    pub font_paths: Vec<PathBuf>,
+    // file names of fonts for characters the configured ones don't have, tried in order
+    #[serde(default)]
+    pub fallback_fonts: Vec<String>,
+    #[serde(default)]
+    pub fonts: Fonts,
+    // regions and widgets of the screen, the built-in layout for the orientation if not set
+    pub file: Option<PathBuf>,
+}
+
+/* how the eink panel is driven */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Panel {
+    // partial updates between two full refreshes that clear the ghosting, 0 for full ones only
+    #[serde(default = "default_full_refresh_every")]
+    pub full_refresh_every: u32,
+    // one of framebuffer::MODELS, detected if not set
+    pub model: Option<String>,
+    // clockwise rotation of the picture in degrees, by default what the model needs to be upright
+    pub rotation: Option<u32>,
+    #[serde(default)]
+    pub waveform: Waveform,
+    // how pictures are reduced to the gray levels of the waveform
+    #[serde(default)]
+    pub dither: Dither,
+}
+
+/* waveform of the partial updates. the fewer gray levels, the quicker the update */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    #[default]
+    Gc16,
+    Gc4,
+    A2,
+}
+
+impl Waveform {
+    pub fn mode(&self) -> Mode {
+        match self {
+            Waveform::Gc16 => Mode::Partial,
+            Waveform::Gc4 => Mode::Gray4,
+            Waveform::A2 => Mode::Fast,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub api_key: String,
+    // scheme, host and optional port of the API, without trailing path
+    pub api_url: String,
+    // PEM bundle to verify the server with instead of the built-in roots
+    pub ca_file: Option<PathBuf>,
+    // plain http leaks the API key, it is only meant for local stub servers
+    pub allow_insecure_http: bool,
+    pub location: Location,
+    pub units: Units,
+    pub lang: String,
+    // seconds between two weather fetches
+    pub refresh_interval: u64,
+    // last good report, shown while the network is down
+    pub cache_file: PathBuf,
+    pub layout: Layout,
+    pub time: LocalTime,
+    pub panel: Panel,
+}
+
+/* the config as it is written in the file. this is checked and turned into a `Config` by
+ * `validate`, so the rest of the program doesn't have to deal with half-specified locations etc.
+ */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    api_key: String,
+    #[serde(default = "default_api_url")]
+    api_url: String,
+    ca_file: Option<PathBuf>,
+    #[serde(default)]
+    allow_insecure_http: bool,
+    location: RawLocation,
+    #[serde(default = "default_units")]
+    units: Units,
+    #[serde(default = "default_lang")]
+    lang: String,
+    #[serde(default = "default_refresh_interval")]
+    refresh_interval: u64,
+    #[serde(default = "default_cache_file")]
+    cache_file: PathBuf,
+    #[serde(default)]
+    layout: Layout,
+    #[serde(default)]
+    time: RawTime,
+    #[serde(default)]
+    panel: Panel,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawLocation {
+    lat: Option<f32>,
+    lon: Option<f32>,
+    city: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTime {
+    zone: Option<String>,
+    #[serde(default = "default_locale")]
+    locale: String,
+    #[serde(default = "default_time_format")]
+    format: String,
+    #[serde(default = "default_time_of_day_format")]
+    time_format: String,
+}
+
+impl Config {
+    /* path of the config file: --config on the command line wins over the environment variable,
+     * which wins over the default in the working directory.
+     */
+    pub fn path(cli: Option<String>) -> PathBuf {
+        cli.or_else(|| std::env::var(ENV_VAR).ok())
+            .unwrap_or_else(|| DEFAULT_PATH.to_string())
+            .into()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_owned(), e))?;
+        Config::parse(&text).map_err(|e| match e {
+            ConfigError::Parse(_, e) => ConfigError::Parse(path.to_owned(), e),
+            e => e,
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let raw: RawConfig =
+            toml::from_str(text).map_err(|e| ConfigError::Parse(PathBuf::new(), e))?;
+        raw.validate()
+    }
+}
+
+impl RawConfig {
+    fn validate(self) -> Result<Config, ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Invalid(msg.to_string()));
+
+        if self.api_key.trim().is_empty() {
+            return invalid("api_key must not be empty");
+        }
+
+        match Url::parse(&self.api_url) {
+            Ok(url) if !url.tls && !self.allow_insecure_http => {
+                return invalid(
+                    "api_url uses plain http, set allow_insecure_http = true to allow it",
+                )
+            }
+            Ok(_) => {}
+            Err(e) => return invalid(&format!("api_url: {}", e)),
+        }
+
+        let location = match self.location {
+            RawLocation {
+                lat: Some(lat),
+                lon: Some(lon),
+                city: None,
+            } => {
+                if !(-90.0..=90.0).contains(&lat) {
+                    return invalid("location.lat must be between -90 and 90");
+                }
+                if !(-180.0..=180.0).contains(&lon) {
+                    return invalid("location.lon must be between -180 and 180");
+                }
+                Location::Coordinates { lat, lon }
+            }
+            RawLocation {
+                lat: None,
+                lon: None,
+                city: Some(city),
+            } => {
+                if city.trim().is_empty() {
+                    return invalid("location.city must not be empty");
+                }
+                Location::City(city)
+            }
+            RawLocation { city: Some(_), .. } => {
+                return invalid("location needs either city or lat/lon, not both")
+            }
+            _ => return invalid("location needs both lat and lon, or a city"),
+        };
+
+        if self.lang.trim().is_empty() {
+            return invalid("lang must not be empty");
+        }
+
+        if self.refresh_interval == 0 {
+            return invalid("refresh_interval must be at least one second");
+        }
+        // a day old weather is of no use, and the daemon can't wait past the end of time
+        if self.refresh_interval > MAX_REFRESH_INTERVAL {
+            return invalid("refresh_interval must be at most a day (86400 seconds)");
+        }
+
+        let fonts = &self.layout.fonts;
+        for (name, font) in [
+            ("big", &fonts.big),
+            ("med", &fonts.med),
+            ("small", &fonts.small),
+        ] {
+            if font.name.is_empty() {
+                return invalid(&format!("layout.fonts.{}.name must not be empty", name));
+            }
+            if font.size <= 0.0 {
+                return invalid(&format!("layout.fonts.{}.size must be positive", name));
+            }
+            if !(0.0..=1.0).contains(&font.saturation) {
+                return invalid(&format!(
+                    "layout.fonts.{}.saturation must be between 0 and 1",
+                    name
+                ));
+            }
+        }
+
+        if let Some(model) = &self.panel.model {
+            if Model::by_name(model).is_none() {
+                let known: Vec<_> = framebuffer::MODELS.iter().map(|m| m.name).collect();
+                return invalid(&format!(
+                    "panel.model \"{}\" is unknown, use one of {}",
+                    model,
+                    known.join(", ")
+                ));
+            }
+        }
+
+        if let Some(rotation) = self.panel.rotation {
+            if Rotation::from_degrees(rotation).is_none() {
+                return invalid("panel.rotation must be 0, 90, 180 or 270");
+            }
+        }
+
+        let time = LocalTime::new(
+            self.time.zone.as_deref(),
+            &self.time.locale,
+            self.time.format,
+            self.time.time_format,
+        )
+        .map_err(|e| ConfigError::Invalid(format!("time: {}", e)))?;
+
+        Ok(Config {
+            api_key: self.api_key,
+            api_url: self.api_url,
+            ca_file: self.ca_file,
+            allow_insecure_http: self.allow_insecure_http,
+            location,
+            units: self.units,
+            lang: self.lang,
+            refresh_interval: self.refresh_interval,
+            cache_file: self.cache_file,
+            layout: self.layout,
+            time,
+            panel: self.panel,
+        })
+    }
+}
+
+fn default_api_url() -> String {
+    "https://api.openweathermap.org".to_string()
+}
+
+fn default_units() -> Units {
+    Units::Metric
+}
+
+fn default_lang() -> String {
+    "en".to_string()
+}
+
+fn default_refresh_interval() -> u64 {
+    30 * 60
+}
+
+fn default_cache_file() -> PathBuf {
+    "kobors-cache.json".into()
+}
+
+fn default_locale() -> String {
+    localtime::DEFAULT_LOCALE.to_string()
+}
+
+fn default_time_format() -> String {
+    localtime::DEFAULT_FORMAT.to_string()
+}
+
+fn default_time_of_day_format() -> String {
+    localtime::DEFAULT_TIME_FORMAT.to_string()
+}
+
+fn default_full_refresh_every() -> u32 {
+    screen::FULL_REFRESH_EVERY
+}
+
+fn default_margin() -> u32 {
+    10
+}
+
+fn default_icons() -> PathBuf {
+    "icons".into()
+}
+
+fn default_font(size: f32) -> FontSetting {
+    FontSetting {
+        name: DEFAULT_FONT.to_string(),
+        size,
+        saturation: 1.0,
+    }
+}
+
+fn default_font_big() -> FontSetting {
+    default_font(192.0)
+}
+
+fn default_font_med() -> FontSetting {
+    default_font(96.0)
+}
+
+fn default_font_small() -> FontSetting {
+    default_font(32.0)
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts {
+            big: default_font_big(),
+            med: default_font_med(),
+            small: default_font_small(),
+        }
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            margin: default_margin(),
+            icons: default_icons(),
+            font_paths: Vec::new(),
+            fallback_fonts: Vec::new(),
+            fonts: Fonts::default(),
+            file: None,
+        }
+    }
+}
+
+impl Default for RawTime {
+    fn default() -> Self {
+        RawTime {
+            zone: None,
+            locale: default_locale(),
+            format: default_time_format(),
+            time_format: default_time_of_day_format(),
+        }
+    }
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Panel {
+            full_refresh_every: default_full_refresh_every(),
+            model: None,
+            rotation: None,
+            waveform: Waveform::default(),
+            dither: Dither::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = "api_key = \"key\"\n[location]\ncity = \"Hildesheim\"\n";
+
+    // the message of the error `text` is refused with
+    fn refused(text: &str) -> String {
+        match Config::parse(text) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("accepted {}", text),
+        }
+    }
+
+    #[test]
+    fn takes_a_city_or_coordinates() {
+        let config = Config::parse(MINIMAL).unwrap();
+        assert!(matches!(config.location, Location::City(ref city) if city == "Hildesheim"));
+        let config = Config::parse("api_key = \"key\"\n[location]\nlat = -33.9\nlon = 18.4\n");
+        assert!(matches!(
+            config.unwrap().location,
+            Location::Coordinates { lat, lon } if lat == -33.9 && lon == 18.4
+        ));
+
+        let both = "api_key = \"key\"\n[location]\ncity = \"Hildesheim\"\nlat = 52.2\nlon = 9.9\n";
+        assert!(refused(both).contains("either city or lat/lon, not both"));
+        let half = "api_key = \"key\"\n[location]\nlat = 52.2\n";
+        assert!(refused(half).contains("needs both lat and lon, or a city"));
+        let empty = "api_key = \"key\"\n[location]\ncity = \" \"\n";
+        assert!(refused(empty).contains("location.city must not be empty"));
+    }
+
+    #[test]
+    fn checks_coordinates_are_on_earth() {
+        let lat = "api_key = \"key\"\n[location]\nlat = 90.5\nlon = 9.9\n";
+        assert!(refused(lat).contains("location.lat must be between -90 and 90"));
+        let lon = "api_key = \"key\"\n[location]\nlat = 52.2\nlon = -180.5\n";
+        assert!(refused(lon).contains("location.lon must be between -180 and 180"));
+    }
+
+    #[test]
+    fn refuses_plain_http_unless_allowed() {
+        let http = format!("api_url = \"http://localhost:8080\"\n{}", MINIMAL);
+        assert!(refused(&http).contains("set allow_insecure_http = true"));
+        let allowed = format!("allow_insecure_http = true\n{}", http);
+        assert!(Config::parse(&allowed).unwrap().allow_insecure_http);
+
+        let broken = format!("api_url = \"ftp://localhost\"\n{}", MINIMAL);
+        assert!(refused(&broken).starts_with("invalid config: api_url: "));
+    }
+
+    #[test]
+    fn checks_the_panel() {
+        let model = format!("{}[panel]\nmodel = \"kindle\"\n", MINIMAL);
+        assert!(refused(&model).contains("panel.model \"kindle\" is unknown, use one of "));
+        let name = framebuffer::MODELS[0].name;
+        let known = format!("{}[panel]\nmodel = \"{}\"\n", MINIMAL, name);
+        assert!(Config::parse(&known).is_ok());
+
+        let rotation = format!("{}[panel]\nrotation = 45\n", MINIMAL);
+        assert!(refused(&rotation).contains("panel.rotation must be 0, 90, 180 or 270"));
+        let rotation = format!("{}[panel]\nrotation = 270\n", MINIMAL);
+        assert_eq!(Config::parse(&rotation).unwrap().panel.rotation, Some(270));
+    }
+
+    #[test]
+    fn bounds_the_refresh_interval() {
+        let zero = format!("refresh_interval = 0\n{}", MINIMAL);
+        assert!(refused(&zero).contains("refresh_interval must be at least one second"));
+        assert_eq!(Config::parse(MINIMAL).unwrap().refresh_interval, 30 * 60);
+
+        let forever = format!("refresh_interval = 9223372036854775807\n{}", MINIMAL);
+        assert!(refused(&forever).contains("refresh_interval must be at most a day"));
+        let day = format!("refresh_interval = 86400\n{}", MINIMAL);
+        assert_eq!(Config::parse(&day).unwrap().refresh_interval, 86400);
+    }
+
+    #[test]
+    fn refuses_unknown_fields() {
+        // e.g. a typo that would otherwise silently use the default
+        let typo = format!("refresh_intervall = 60\n{}", MINIMAL);
+        assert!(refused(&typo).contains("unknown field `refresh_intervall`"));
+        let nested = format!("{}[panel]\nwaveforms = \"a2\"\n", MINIMAL);
+        assert!(refused(&nested).contains("unknown field `waveforms`"));
+    }
+
+    #[test]
+    fn finds_the_config_file() {
+        std::env::remove_var(ENV_VAR);
+        assert_eq!(Config::path(None), Path::new(DEFAULT_PATH));
+        std::env::set_var(ENV_VAR, "/etc/kobors.toml");
+        assert_eq!(Config::path(None), Path::new("/etc/kobors.toml"));
+        assert_eq!(
+            Config::path(Some("cli.toml".to_string())),
+            Path::new("cli.toml")
+        );
+        std::env::remove_var(ENV_VAR);
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// how long we sleep at most before checking whether we were asked to stop
+const TICK: Duration = Duration::from_secs(1);
+
+/* source of time for the refresh loop, so the loop can be driven without real sleeps */
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/* fetch, and if the data differs from what is on screen, render. repeats every `interval` until
+ * `stop` is set. a failed fetch is only logged as long as there is a good frame on the display,
+ * which is better than an empty screen until the network is back. if there is none, or rendering
+ * fails, `fail` is called to show the error.
+ */
+pub fn run<C, T, E, F, R, X>(
+    clock: &C,
+    interval: Duration,
+    stop: &AtomicBool,
+    mut fetch: F,
+    mut render: R,
+    mut fail: X,
+) where
+    C: Clock,
+    T: PartialEq,
+    E: Display,
+    F: FnMut() -> Result<T, E>,
+    R: FnMut(&T) -> Result<(), E>,
+    X: FnMut(&E),
+{
+    let mut last: Option<T> = None;
+
+    while !stop.load(Ordering::Relaxed) {
+        let deadline = clock.now() + interval;
+
+        match fetch() {
+            Ok(data) if last.as_ref() == Some(&data) => {}
+            Ok(data) => match render(&data) {
+                Ok(()) => last = Some(data),
+                Err(e) => {
+                    eprintln!("rendering failed: {}", e);
+                    fail(&e);
+                    last = None;
+                }
+            },
+            Err(e) if last.is_some() => {
+                eprintln!("fetching weather failed, keeping last frame: {}", e)
+            }
+            Err(e) => {
+                eprintln!("fetching weather failed: {}", e);
+                fail(&e);
+            }
+        }
+
+        while !stop.load(Ordering::Relaxed) {
+            let now = clock.now();
+            if now >= deadline {
+                break;
+            }
+            clock.sleep((deadline - now).min(TICK));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    /* time only moves when the loop sleeps. after `stop_after` sleeps it sets the flag, like a
+     * signal arriving while we wait
+     */
+    struct FakeClock<'a> {
+        now: Cell<Instant>,
+        sleeps: Cell<u32>,
+        stop: &'a AtomicBool,
+        stop_after: u32,
+    }
+
+    impl<'a> FakeClock<'a> {
+        fn new(stop: &'a AtomicBool, stop_after: u32) -> FakeClock<'a> {
+            FakeClock {
+                now: Cell::new(Instant::now()),
+                sleeps: Cell::new(0),
+                stop,
+                stop_after,
+            }
+        }
+    }
+
+    impl Clock for FakeClock<'_> {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            assert!(duration <= TICK);
+            self.now.set(self.now.get() + duration);
+            self.sleeps.set(self.sleeps.get() + 1);
+            if self.sleeps.get() >= self.stop_after {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct Log {
+        rendered: Vec<u32>,
+        failed: Vec<String>,
+    }
+
+    /* runs the loop on `results`, one per interval, and stops after the last one. rendering
+     * fails for odd numbers.
+     */
+    fn run_on(results: Vec<Result<u32, &str>>) -> Log {
+        let stop = AtomicBool::new(false);
+        let clock = FakeClock::new(&stop, u32::MAX);
+        let log = RefCell::new(Log::default());
+        let mut results = results.into_iter();
+
+        run(
+            &clock,
+            INTERVAL,
+            &stop,
+            || {
+                let result = results.next().unwrap().map_err(String::from);
+                if results.len() == 0 {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                result
+            },
+            |data| {
+                log.borrow_mut().rendered.push(*data);
+                match data % 2 {
+                    0 => Ok(()),
+                    _ => Err(format!("cannot render {}", data)),
+                }
+            },
+            |e| log.borrow_mut().failed.push(e.clone()),
+        );
+        log.into_inner()
+    }
+
+    #[test]
+    fn redraws_only_changed_data() {
+        let log = run_on(vec![Ok(2), Ok(2), Ok(4), Ok(4), Ok(2)]);
+        assert_eq!(log.rendered, [2, 4, 2]);
+        assert!(log.failed.is_empty());
+    }
+
+    #[test]
+    fn keeps_last_frame_when_fetching_fails() {
+        let log = run_on(vec![Ok(2), Err("offline"), Err("offline"), Ok(2), Ok(4)]);
+        assert_eq!(log.rendered, [2, 4]);
+        assert!(log.failed.is_empty());
+    }
+
+    #[test]
+    fn fails_without_a_frame() {
+        let log = run_on(vec![Err("offline"), Ok(2)]);
+        assert_eq!(log.rendered, [2]);
+        assert_eq!(log.failed, ["offline"]);
+
+        // a failed render leaves no frame behind, the same data is tried again
+        let log = run_on(vec![Ok(3), Ok(3), Err("offline")]);
+        assert_eq!(log.rendered, [3, 3]);
+        assert_eq!(
+            log.failed,
+            ["cannot render 3", "cannot render 3", "offline"]
+        );
+    }
+
+    #[test]
+    fn fetches_once_per_interval() {
+        let stop = AtomicBool::new(false);
+        // 2.5 intervals of sleeping in ticks
+        let clock = FakeClock::new(&stop, 25);
+        let start = clock.now();
+        let mut fetched = Vec::new();
+
+        run(
+            &clock,
+            INTERVAL,
+            &stop,
+            || {
+                fetched.push(clock.now() - start);
+                Ok::<_, String>(2)
+            },
+            |_| Ok(()),
+            |_| {},
+        );
+        let seconds: Vec<u64> = fetched.iter().map(Duration::as_secs).collect();
+        assert_eq!(seconds, [0, 10, 20]);
+        assert_eq!(clock.now() - start, Duration::from_secs(25));
+    }
+
+    #[test]
+    fn stops_when_asked() {
+        let stop = AtomicBool::new(true);
+        let clock = FakeClock::new(&stop, u32::MAX);
+        run(
+            &clock,
+            INTERVAL,
+            &stop,
+            || -> Result<u32, String> { panic!("fetched after stop") },
+            |_| Ok(()),
+            |_| {},
+        );
+        assert_eq!(clock.sleeps.get(), 0);
+    }
+}
@@ -0,0 +1,1583 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::Snapshot;
+use crate::config::{Config, ConfigError};
+use crate::display::Display;
+use crate::draw::{Figure, Shape, Stroke, Style};
+use crate::error::Error;
+use crate::fonts::FontRegistry;
+use crate::gui::{Align, Drawable, Image, Overflow, Text, TextBox};
+use crate::icons;
+use crate::layout::{Anchor, Grid, Padding, Size, Spacer, Stack, Track};
+use crate::openweather::WeatherReport;
+use crate::region::Region;
+use crate::screen::Screen;
+use crate::util::{Color, FontSetting, Point};
+
+/* The screen as described by a layout file: regions at positions worked out once against the
+ * screen size, and the widgets inside them that are filled with the weather on every draw.
+ * Without a layout file the built-in one for the orientation of the screen is used.
+ */
+
+const PORTRAIT: &str = include_str!("../layouts/portrait.toml");
+const LANDSCAPE: &str = include_str!("../layouts/landscape.toml");
+// weather with long values that every layout is tried with when it is loaded
+const SAMPLE: &str = include_str!("../layouts/sample.json");
+
+/* a length along one axis: pixels, a share of the parent in percent plus pixels, or everything up
+ * to the margin at the far edge of the parent
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "RawLength")]
+struct Length {
+    percent: f32,
+    px: i64,
+    fill: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLength {
+    Px(i64),
+    Expr(String),
+}
+
+impl TryFrom<RawLength> for Length {
+    type Error = String;
+
+    fn try_from(raw: RawLength) -> Result<Self, Self::Error> {
+        let expr = match raw {
+            RawLength::Px(px) => return Ok(Length::px(px)),
+            RawLength::Expr(expr) => expr,
+        };
+        Length::parse(&expr).ok_or_else(|| {
+            format!(
+                "\"{}\" is not a length, use pixels, \"50%\", \"50% - 20\" or \"fill\"",
+                expr
+            )
+        })
+    }
+}
+
+impl Length {
+    const FILL: Length = Length {
+        percent: 0.0,
+        px: 0,
+        fill: true,
+    };
+
+    fn px(px: i64) -> Length {
+        Length {
+            percent: 0.0,
+            px,
+            fill: false,
+        }
+    }
+
+    fn parse(expr: &str) -> Option<Length> {
+        let expr = expr.trim();
+        if expr == "fill" {
+            return Some(Length::FILL);
+        }
+        let Some((percent, rest)) = expr.split_once('%') else {
+            return expr.parse().ok().map(Length::px);
+        };
+        let percent: f32 = percent.trim().parse().ok()?;
+        let rest = rest.trim();
+        let px = match rest.chars().next() {
+            None => 0,
+            Some('+') => rest[1..].trim().parse().ok()?,
+            Some('-') => -rest[1..].trim().parse::<i64>().ok()?,
+            Some(_) => return None,
+        };
+        Some(Length {
+            percent,
+            px,
+            fill: false,
+        })
+    }
+
+    // in a parent of `size` for something that starts at `start`
+    fn resolve(&self, size: u32, start: i64, margin: u32) -> i64 {
+        if self.fill {
+            size as i64 - margin as i64 - start
+        } else {
+            (self.percent * size as f32 / 100.0).round() as i64 + self.px
+        }
+    }
+
+    // for a widget in a parent of `size` with `available` left from where it starts
+    fn within(&self, size: u32, available: u32) -> u32 {
+        if self.fill {
+            available
+        } else {
+            self.resolve(size, 0, 0).max(0) as u32
+        }
+    }
+}
+
+/* where a region or widget goes, at x/y or next to an earlier one */
+#[derive(Debug, Clone)]
+struct Place {
+    x: Option<Length>,
+    y: Option<Length>,
+    below: Option<String>,
+    right_of: Option<String>,
+    gap: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+}
+
+impl Rect {
+    fn pos(&self) -> Point {
+        Point::new(self.x as u32, self.y as u32)
+    }
+}
+
+impl Place {
+    fn check(&self, what: &str, known: &[&str]) -> Result<(), String> {
+        if self.x.is_some_and(|x| x.fill) || self.y.is_some_and(|y| y.fill) {
+            return Err(format!("{}: x and y cannot be \"fill\"", what));
+        }
+        if self.right_of.is_some() && self.x.is_some() {
+            return Err(format!("{}: use either x or right_of", what));
+        }
+        if self.below.is_some() && self.y.is_some() {
+            return Err(format!("{}: use either y or below", what));
+        }
+        for name in self.below.iter().chain(&self.right_of) {
+            if !known.contains(&name.as_str()) {
+                return Err(format!(
+                    "{}: \"{}\" is not the name of an earlier one",
+                    what, name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /* top left corner in a parent of `width`x`height`. below keeps the x of the one above,
+     * right_of the y of the one to the left, anything else starts at the margin
+     */
+    fn origin(
+        &self,
+        width: u32,
+        height: u32,
+        margin: u32,
+        named: &HashMap<String, Rect>,
+    ) -> (i64, i64) {
+        let below = self.below.as_ref().and_then(|n| named.get(n));
+        let right_of = self.right_of.as_ref().and_then(|n| named.get(n));
+        let gap = self.gap as i64;
+
+        let x = match (right_of, self.x, below) {
+            (Some(r), _, _) => r.x + r.width + gap,
+            (None, Some(x), _) => x.resolve(width, 0, margin),
+            (None, None, Some(b)) => b.x,
+            (None, None, None) => margin as i64,
+        };
+        let y = match (below, self.y, right_of) {
+            (Some(b), _, _) => b.y + b.height + gap,
+            (None, Some(y), _) => y.resolve(height, 0, margin),
+            (None, None, Some(r)) => r.y,
+            (None, None, None) => margin as i64,
+        };
+        (x, y)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum When {
+    #[default]
+    Always,
+    // only with a report fresh from the network
+    Fresh,
+    // only with a cached report while the network is down
+    Stale,
+}
+
+impl When {
+    fn matches(&self, stale: bool) -> bool {
+        match self {
+            When::Always => true,
+            When::Fresh => !stale,
+            When::Stale => stale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FontName {
+    Big,
+    Med,
+    #[default]
+    Small,
+}
+
+impl FontName {
+    // at the size for a screen `scale` times the one the layout was made for
+    fn setting(&self, config: &Config, scale: f32) -> FontSetting {
+        let fonts = &config.layout.fonts;
+        let font = match self {
+            FontName::Big => &fonts.big,
+            FontName::Med => &fonts.med,
+            FontName::Small => &fonts.small,
+        };
+        FontSetting {
+            size: font.size * scale,
+            ..font.clone()
+        }
+    }
+}
+
+/* the values of the current weather that text widgets can show */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Location,
+    Name,
+    Country,
+    Datetime,
+    Temp,
+    FeelsLike,
+    TempMin,
+    TempMax,
+    Unit,
+    Humidity,
+    Pressure,
+    Clouds,
+    Visibility,
+    Wind,
+    WindDir,
+    WindSpeed,
+    SpeedUnit,
+    Gust,
+    Rain,
+    Snow,
+    Condition,
+    Description,
+    Sunrise,
+    Sunset,
+    FetchedAt,
+}
+
+const FIELDS: [(&str, Field); 25] = [
+    ("location", Field::Location),
+    ("name", Field::Name),
+    ("country", Field::Country),
+    ("datetime", Field::Datetime),
+    ("temp", Field::Temp),
+    ("feels_like", Field::FeelsLike),
+    ("temp_min", Field::TempMin),
+    ("temp_max", Field::TempMax),
+    ("unit", Field::Unit),
+    ("humidity", Field::Humidity),
+    ("pressure", Field::Pressure),
+    ("clouds", Field::Clouds),
+    ("visibility", Field::Visibility),
+    ("wind", Field::Wind),
+    ("wind_dir", Field::WindDir),
+    ("wind_speed", Field::WindSpeed),
+    ("speed_unit", Field::SpeedUnit),
+    ("gust", Field::Gust),
+    ("rain", Field::Rain),
+    ("snow", Field::Snow),
+    ("condition", Field::Condition),
+    ("description", Field::Description),
+    ("sunrise", Field::Sunrise),
+    ("sunset", Field::Sunset),
+    ("fetched_at", Field::FetchedAt),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Literal(String),
+    Field(Field),
+}
+
+/* text with {field} placeholders, {{ and }} for the braces themselves */
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+struct Template(Vec<Piece>);
+
+impl TryFrom<String> for Template {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    let Some((_, field)) = FIELDS.iter().find(|(n, _)| *n == name.trim()) else {
+                        let known: Vec<_> = FIELDS.iter().map(|(n, _)| *n).collect();
+                        return Err(format!(
+                            "unknown field {{{}}} in \"{}\", use one of {}",
+                            name,
+                            text,
+                            known.join(", ")
+                        ));
+                    };
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(*field));
+                }
+                '}' => return Err(format!("unmatched }} in \"{}\"", text)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Template(pieces))
+    }
+}
+
+impl Template {
+    // None if one of the fields has no value
+    fn render(&self, values: &Values) -> Option<String> {
+        let mut text = String::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Literal(s) => text += s,
+                Piece::Field(field) => text += &values.get(*field)?,
+            }
+        }
+        Some(text)
+    }
+}
+
+/* the weather of a snapshot as text, the way the widgets show it */
+struct Values<'a> {
+    config: &'a Config,
+    snapshot: &'a Snapshot,
+}
+
+impl<'a> Values<'a> {
+    fn get(&self, field: Field) -> Option<String> {
+        let weather = &self.snapshot.report.current;
+        let time = |t: i64| self.config.time.time(t, weather.timezone);
+        // 0 during polar night and midnight sun, the sun doesn't rise or set that day
+        let sun = |t: i64| Some(t).filter(|t| *t != 0).map(time);
+        let units = self.config.units;
+        // coordinates over the ocean have neither a city name nor a country
+        let name = Some(weather.name.trim()).filter(|s| !s.is_empty());
+        let country = weather
+            .sys
+            .country
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty());
+        let condition = weather.weather.first();
+
+        let value = match field {
+            Field::Location => {
+                let datetime = self.config.time.datetime(weather.dt, weather.timezone);
+                [name, country, Some(datetime.trim())]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+            Field::Name => name?.to_string(),
+            Field::Country => country?.to_string(),
+            Field::Datetime => self.config.time.datetime(weather.dt, weather.timezone),
+            Field::Temp => format!("{:.1}", weather.main.temp),
+            Field::FeelsLike => format!("{:.1}", weather.main.feels_like),
+            Field::TempMin => format!("{:.1}", weather.main.temp_min),
+            Field::TempMax => format!("{:.1}", weather.main.temp_max),
+            Field::Unit => units.temperature().to_string(),
+            Field::Humidity => weather.main.humidity.to_string(),
+            Field::Pressure => weather.main.pressure.to_string(),
+            Field::Clouds => weather.clouds.all.to_string(),
+            // in km, the API gives meters
+            Field::Visibility => format!("{:.1}", weather.visibility? as f32 / 1000.0),
+            Field::Wind => {
+                let wind = &weather.wind;
+                let mut text = format!("{} {:.1} {}", wind.cardinal(), wind.speed, units.speed());
+                if let Some(gust) = wind.gust {
+                    text += &format!(", gusts {:.1}", gust);
+                }
+                text
+            }
+            Field::WindDir => weather.wind.cardinal().to_string(),
+            Field::WindSpeed => format!("{:.1}", weather.wind.speed),
+            Field::SpeedUnit => units.speed().to_string(),
+            Field::Gust => format!("{:.1}", weather.wind.gust?),
+            // of the last hour, in mm
+            Field::Rain => format!("{:.1}", weather.rain.as_ref()?.one_hour?),
+            Field::Snow => format!("{:.1}", weather.snow.as_ref()?.one_hour?),
+            Field::Condition => condition?.main.clone(),
+            Field::Description => condition?.description.clone(),
+            Field::Sunrise => sun(weather.sys.sunrise)?,
+            Field::Sunset => sun(weather.sys.sunset)?,
+            Field::FetchedAt => time(self.snapshot.fetched_at),
+        };
+        Some(value)
+    }
+}
+
+/* the layout file as it is written. checked and turned into a `Dashboard` by `Dashboard::load` */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDashboard {
+    // width and height of the screen the fonts and icons are sized for
+    reference: Option<[u32; 2]>,
+    #[serde(rename = "region", default)]
+    regions: Vec<RawRegion>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRegion {
+    name: String,
+    x: Option<Length>,
+    y: Option<Length>,
+    below: Option<String>,
+    right_of: Option<String>,
+    #[serde(default = "default_gap")]
+    gap: u32,
+    width: Option<Length>,
+    height: Option<Length>,
+    #[serde(default = "default_border")]
+    border: bool,
+    #[serde(rename = "widget", default)]
+    widgets: Vec<RawWidget>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Text,
+    Icon,
+    Forecast,
+    List,
+    Row,
+    Column,
+    Grid,
+    Shape,
+}
+
+/* what a shape widget draws */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ShapeKind {
+    Line,
+    Rect,
+    Circle,
+    Arc,
+    Polygon,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawWidget {
+    #[serde(rename = "type")]
+    kind: Kind,
+    name: Option<String>,
+    #[serde(default)]
+    when: When,
+    x: Option<Length>,
+    y: Option<Length>,
+    below: Option<String>,
+    right_of: Option<String>,
+    // to the widget it is placed next to, and between the parts of lists, forecasts and containers
+    #[serde(default = "default_gap")]
+    gap: u32,
+    width: Option<Length>,
+    height: Option<Length>,
+    #[serde(default)]
+    font: FontName,
+    text: Option<Template>,
+    wrap: Option<bool>,
+    align: Option<Align>,
+    valign: Option<Align>,
+    line_spacing: Option<f32>,
+    overflow: Option<Overflow>,
+    file: Option<PathBuf>,
+    scale: Option<f32>,
+    days: Option<usize>,
+    icon_width: Option<u32>,
+    #[serde(rename = "row", default)]
+    rows: Vec<Row>,
+    padding: Option<u32>,
+    border: Option<bool>,
+    anchor: Option<Anchor>,
+    columns: Option<Vec<Column>>,
+    #[serde(rename = "widget", default)]
+    widgets: Vec<RawWidget>,
+    shape: Option<ShapeKind>,
+    // of lines and polygons, in pixels from where the widget is
+    points: Option<Vec<[u32; 2]>>,
+    radius: Option<u32>,
+    // of arcs in degrees, clockwise from the top
+    start: Option<f32>,
+    end: Option<f32>,
+    // width of the outline and gray levels of it and the inside
+    stroke: Option<f32>,
+    color: Option<u8>,
+    fill: Option<u8>,
+    dash: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Row {
+    // file in the icon directory, shown left of the text
+    icon: Option<PathBuf>,
+    text: Template,
+}
+
+/* a column of a grid as written: pixels, or a share of the rest like "1fr" */
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "RawLength")]
+struct Column(Track);
+
+impl TryFrom<RawLength> for Column {
+    type Error = String;
+
+    fn try_from(raw: RawLength) -> Result<Self, Self::Error> {
+        match raw {
+            RawLength::Px(px) if px >= 0 => Ok(Column(Track::Px(px as u32))),
+            RawLength::Px(px) => Err(format!("column width {} is negative", px)),
+            RawLength::Expr(expr) => expr
+                .trim()
+                .strip_suffix("fr")
+                .and_then(|weight| weight.trim().parse::<f32>().ok())
+                .filter(|weight| *weight > 0.0)
+                .map(|weight| Column(Track::Weight(weight)))
+                .ok_or_else(|| format!("\"{}\" is not a column, use pixels or \"1fr\"", expr)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Arrangement {
+    Row,
+    Column,
+    Grid(Vec<Track>),
+}
+
+#[derive(Debug, Clone)]
+enum Content {
+    // a single line as wide as it is, or a box of text if it has a width
+    Text {
+        template: Template,
+        font: FontName,
+        width: Option<Length>,
+        height: Option<Length>,
+        wrap: bool,
+        align: Align,
+        valign: Align,
+        line_spacing: f32,
+        overflow: Overflow,
+    },
+    // the icon of the current condition without a file
+    Icon {
+        file: Option<PathBuf>,
+        scale: f32,
+    },
+    // one column per day: weekday, icon, max/min, precipitation and its probability
+    Forecast {
+        font: FontName,
+        days: usize,
+        scale: f32,
+        width: Option<Length>,
+    },
+    // one line of text per row, after a column for the icons
+    List {
+        font: FontName,
+        rows: Vec<Row>,
+        icon_width: u32,
+        width: Option<Length>,
+    },
+    // other widgets one after the other or in a grid, with a border around them if asked for
+    Container {
+        arrangement: Arrangement,
+        padding: u32,
+        border: bool,
+        anchor: Anchor,
+        width: Option<Length>,
+        children: Vec<Widget>,
+    },
+    // lines and polygons through points, rectangles of width and height, circles and arcs
+    Shape {
+        shape: ShapeKind,
+        points: Vec<Point>,
+        width: Option<Length>,
+        height: Option<Length>,
+        radius: u32,
+        start: f32,
+        end: f32,
+        style: Style,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Widget {
+    name: Option<String>,
+    // "region <name>, widget <name or number>" for errors
+    label: String,
+    when: When,
+    place: Place,
+    content: Content,
+}
+
+#[derive(Debug, Clone)]
+struct DashboardRegion {
+    name: String,
+    rect: Rect,
+    border: bool,
+    widgets: Vec<Widget>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    regions: Vec<DashboardRegion>,
+    // of fonts and icons, on screens smaller or bigger than the reference of the layout
+    scale: f32,
+}
+
+impl Dashboard {
+    /* reads the layout `file`, or takes the built-in one for the orientation, and places its
+     * regions on a screen of `width`x`height`. the widgets are tried with the sample weather, one
+     * that doesn't fit into its region is an error here rather than cut off on every draw
+     */
+    pub fn load(
+        file: Option<&Path>,
+        width: u32,
+        height: u32,
+        config: &Config,
+        registry: &FontRegistry,
+    ) -> Result<Dashboard, Error> {
+        let (path, text) = match file {
+            Some(path) => (
+                path.to_owned(),
+                std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_owned(), e))?,
+            ),
+            None if width > height => (
+                PathBuf::from("<built-in landscape layout>"),
+                LANDSCAPE.to_string(),
+            ),
+            None => (
+                PathBuf::from("<built-in portrait layout>"),
+                PORTRAIT.to_string(),
+            ),
+        };
+        Dashboard::parse(&path, &text, width, height, config, registry)
+    }
+
+    fn parse(
+        path: &Path,
+        text: &str,
+        width: u32,
+        height: u32,
+        config: &Config,
+        registry: &FontRegistry,
+    ) -> Result<Dashboard, Error> {
+        let invalid =
+            |msg: String| ConfigError::Invalid(format!("layout {}: {}", path.display(), msg));
+        let raw: RawDashboard =
+            toml::from_str(text).map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+        let dashboard = raw
+            .validate(width, height, config.layout.margin)
+            .map_err(invalid)?;
+
+        let report: WeatherReport = serde_json::from_str(SAMPLE).expect("sample weather");
+        for stale in [false, true] {
+            let snapshot = Snapshot {
+                report: report.clone(),
+                fetched_at: report.current.dt,
+                stale,
+            };
+            let cx = Context {
+                registry,
+                config,
+                values: Values {
+                    config,
+                    snapshot: &snapshot,
+                },
+                scale: dashboard.scale,
+                strict: true,
+            };
+            dashboard.regions(&cx).map_err(|e| match e {
+                Error::Config(ConfigError::Invalid(msg)) => Error::Config(invalid(msg)),
+                e => e,
+            })?;
+        }
+        Ok(dashboard)
+    }
+
+    /* fills the widgets with `snapshot` and draws the whole screen */
+    pub fn draw<D: Display>(
+        &self,
+        screen: &Screen<D>,
+        registry: &FontRegistry,
+        config: &Config,
+        snapshot: &Snapshot,
+    ) -> Result<(), Error> {
+        let cx = Context {
+            registry,
+            config,
+            values: Values { config, snapshot },
+            scale: self.scale,
+            strict: false,
+        };
+        let regions = self.regions(&cx)?;
+
+        screen.remove_regions();
+        for region in regions {
+            screen.add_region(region);
+        }
+        screen.clear();
+        screen.render()?;
+        screen.update()
+    }
+
+    fn regions(&self, cx: &Context) -> Result<Vec<Region>, Error> {
+        let mut regions = Vec::new();
+        for spec in &self.regions {
+            let rect = spec.rect;
+            let mut region = Region::new(
+                rect.pos(),
+                rect.width as u32,
+                rect.height as u32,
+                spec.border,
+            );
+            let mut named = HashMap::new();
+            for widget in &spec.widgets {
+                let placed = widget.add_to(&mut region, cx, &named)?;
+                if let Some(name) = &widget.name {
+                    named.insert(name.clone(), placed);
+                }
+            }
+            regions.push(region);
+        }
+        Ok(regions)
+    }
+}
+
+impl RawDashboard {
+    fn validate(self, width: u32, height: u32, margin: u32) -> Result<Dashboard, String> {
+        if self.regions.is_empty() {
+            return Err("there are no regions".to_string());
+        }
+        let scale = match self.reference {
+            Some([w, h]) if w == 0 || h == 0 => {
+                return Err(format!("the reference screen of {}x{} has no room", w, h));
+            }
+            Some([w, h]) => (width as f32 / w as f32).min(height as f32 / h as f32),
+            None => 1.0,
+        };
+
+        let mut regions: Vec<DashboardRegion> = Vec::new();
+        let mut named = HashMap::new();
+        for raw in self.regions {
+            let what = format!("region {}", raw.name);
+            let known: Vec<_> = regions.iter().map(|r| r.name.as_str()).collect();
+            if known.contains(&raw.name.as_str()) {
+                return Err(format!("{} is there twice", what));
+            }
+            let place = Place {
+                x: raw.x,
+                y: raw.y,
+                below: raw.below,
+                right_of: raw.right_of,
+                gap: raw.gap,
+            };
+            place.check(&what, &known)?;
+
+            let (x, y) = place.origin(width, height, margin, &named);
+            let rect = Rect {
+                x,
+                y,
+                width: raw.width.unwrap_or(Length::FILL).resolve(width, x, margin),
+                height: raw
+                    .height
+                    .unwrap_or(Length::FILL)
+                    .resolve(height, y, margin),
+            };
+            if rect.width <= 0 || rect.height <= 0 {
+                return Err(format!(
+                    "{} is {}x{}, it needs some room",
+                    what, rect.width, rect.height
+                ));
+            }
+            if rect.x < 0
+                || rect.y < 0
+                || rect.x + rect.width > width as i64
+                || rect.y + rect.height > height as i64
+            {
+                return Err(format!(
+                    "{} at {},{} of {}x{} is not on the {}x{} screen",
+                    what, rect.x, rect.y, rect.width, rect.height, width, height
+                ));
+            }
+
+            let mut widgets: Vec<Widget> = Vec::new();
+            for (i, raw) in raw.widgets.into_iter().enumerate() {
+                let name = raw.name.clone().unwrap_or((i + 1).to_string());
+                let what = format!("{}, widget {}", what, name);
+                let known: Vec<_> = widgets.iter().filter_map(|w| w.name.as_deref()).collect();
+                if raw
+                    .name
+                    .as_ref()
+                    .is_some_and(|n| known.contains(&n.as_str()))
+                {
+                    return Err(format!("{} is there twice", what));
+                }
+                widgets.push(raw.validate(what, Some(&known))?);
+            }
+
+            regions.push(DashboardRegion {
+                name: raw.name,
+                rect,
+                border: raw.border,
+                widgets,
+            });
+            named.insert(regions.last().unwrap().name.clone(), rect);
+        }
+        Ok(Dashboard { regions, scale })
+    }
+}
+
+impl RawWidget {
+    /* `known` are the names of the widgets before it in the region, None for the children of a
+     * container, which places them itself
+     */
+    fn validate(self, what: String, known: Option<&[&str]>) -> Result<Widget, String> {
+        let place = Place {
+            x: self.x,
+            y: self.y,
+            below: self.below,
+            right_of: self.right_of,
+            gap: self.gap,
+        };
+        match known {
+            Some(known) => place.check(&what, known)?,
+            None => {
+                if place.x.is_some()
+                    || place.y.is_some()
+                    || place.below.is_some()
+                    || place.right_of.is_some()
+                {
+                    return Err(format!("{}: is placed by its container", what));
+                }
+            }
+        }
+
+        let unused = |set: bool, field: &str| {
+            if set {
+                Err(format!(
+                    "{}: {} is not used by {} widgets",
+                    what,
+                    field,
+                    format!("{:?}", self.kind).to_lowercase()
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        let container = matches!(self.kind, Kind::Row | Kind::Column | Kind::Grid);
+        let text_only = [
+            (self.text.is_some(), "text"),
+            (self.wrap.is_some(), "wrap"),
+            (self.align.is_some(), "align"),
+            (self.valign.is_some(), "valign"),
+            (self.line_spacing.is_some(), "line_spacing"),
+            (self.overflow.is_some(), "overflow"),
+        ];
+        if self.kind != Kind::Text {
+            for (set, field) in text_only {
+                unused(set, field)?;
+            }
+        }
+        if self.kind != Kind::Icon {
+            unused(self.file.is_some(), "file")?;
+        }
+        if self.kind != Kind::Forecast {
+            unused(self.days.is_some(), "days")?;
+        }
+        if self.kind != Kind::List {
+            unused(self.icon_width.is_some(), "icon_width")?;
+            unused(!self.rows.is_empty(), "row")?;
+        }
+        if self.kind != Kind::Grid {
+            unused(self.columns.is_some(), "columns")?;
+        }
+        if self.kind != Kind::Shape {
+            let shape_only = [
+                (self.shape.is_some(), "shape"),
+                (self.points.is_some(), "points"),
+                (self.radius.is_some(), "radius"),
+                (self.start.is_some(), "start"),
+                (self.end.is_some(), "end"),
+                (self.stroke.is_some(), "stroke"),
+                (self.color.is_some(), "color"),
+                (self.fill.is_some(), "fill"),
+                (self.dash.is_some(), "dash"),
+            ];
+            for (set, field) in shape_only {
+                unused(set, field)?;
+            }
+        }
+        if !container {
+            unused(self.padding.is_some(), "padding")?;
+            unused(self.border.is_some(), "border")?;
+            unused(self.anchor.is_some(), "anchor")?;
+            unused(!self.widgets.is_empty(), "widget")?;
+        }
+        if matches!(self.kind, Kind::Text | Kind::List | Kind::Shape) || container {
+            unused(self.scale.is_some(), "scale")?;
+        }
+        if !matches!(self.kind, Kind::Text | Kind::Shape) {
+            unused(self.height.is_some(), "height")?;
+        }
+        if self.kind == Kind::Icon {
+            unused(self.width.is_some(), "width")?;
+        }
+        if self.scale.is_some_and(|s| s <= 0.0) {
+            return Err(format!("{}: scale must be positive", what));
+        }
+
+        let content = match self.kind {
+            Kind::Text => {
+                let Some(template) = self.text else {
+                    return Err(format!("{}: text widgets need a text", what));
+                };
+                if self.width.is_none() && (self.height.is_some() || self.wrap == Some(true)) {
+                    return Err(format!("{}: height and wrap need a width", what));
+                }
+                let line_spacing = self.line_spacing.unwrap_or(1.0);
+                if line_spacing <= 0.0 {
+                    return Err(format!("{}: line_spacing must be positive", what));
+                }
+                Content::Text {
+                    template,
+                    font: self.font,
+                    width: self.width,
+                    height: self.height,
+                    wrap: self.wrap.unwrap_or(false),
+                    align: self.align.unwrap_or_default(),
+                    valign: self.valign.unwrap_or_default(),
+                    line_spacing,
+                    overflow: self.overflow.unwrap_or_default(),
+                }
+            }
+            Kind::Icon => Content::Icon {
+                file: self.file,
+                scale: self.scale.unwrap_or(1.0),
+            },
+            Kind::Forecast => {
+                let days = self.days.unwrap_or(5);
+                if days == 0 {
+                    return Err(format!("{}: days must be at least 1", what));
+                }
+                Content::Forecast {
+                    font: self.font,
+                    days,
+                    scale: self.scale.unwrap_or(1.5),
+                    width: self.width,
+                }
+            }
+            Kind::List => {
+                if self.rows.is_empty() {
+                    return Err(format!("{}: list widgets need at least one row", what));
+                }
+                Content::List {
+                    font: self.font,
+                    rows: self.rows,
+                    // the bundled icons are at most 64px wide
+                    icon_width: self.icon_width.unwrap_or(64),
+                    width: self.width,
+                }
+            }
+            Kind::Row | Kind::Column | Kind::Grid => {
+                let arrangement = match self.kind {
+                    Kind::Row => Arrangement::Row,
+                    Kind::Column => Arrangement::Column,
+                    _ => match self.columns {
+                        Some(columns) if !columns.is_empty() => {
+                            Arrangement::Grid(columns.into_iter().map(|c| c.0).collect())
+                        }
+                        _ => return Err(format!("{}: grids need columns", what)),
+                    },
+                };
+                if self.widgets.is_empty() {
+                    return Err(format!("{}: containers need at least one widget", what));
+                }
+                let mut children = Vec::new();
+                for (i, raw) in self.widgets.into_iter().enumerate() {
+                    let name = raw.name.clone().unwrap_or((i + 1).to_string());
+                    children.push(raw.validate(format!("{}, widget {}", what, name), None)?);
+                }
+                Content::Container {
+                    arrangement,
+                    padding: self.padding.unwrap_or(0),
+                    border: self.border.unwrap_or(false),
+                    anchor: self.anchor.unwrap_or_default(),
+                    width: self.width,
+                    children,
+                }
+            }
+            Kind::Shape => {
+                let Some(shape) = self.shape else {
+                    return Err(format!("{}: shape widgets need a shape", what));
+                };
+                let points: Vec<Point> = self
+                    .points
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|[x, y]| Point::new(x, y))
+                    .collect();
+                let needed = match shape {
+                    ShapeKind::Line => 2,
+                    ShapeKind::Polygon => 3,
+                    _ => 0,
+                };
+                if needed == 0 {
+                    unused(!points.is_empty(), "points")?;
+                } else if points.len() < needed {
+                    return Err(format!("{}: needs at least {} points", what, needed));
+                }
+                if shape != ShapeKind::Rect {
+                    unused(self.width.is_some(), "width")?;
+                    unused(self.height.is_some(), "height")?;
+                } else if self.width.is_none() || self.height.is_none() {
+                    return Err(format!("{}: rectangles need a width and a height", what));
+                }
+                match shape {
+                    ShapeKind::Line | ShapeKind::Polygon => {
+                        unused(self.radius.is_some(), "radius")?
+                    }
+                    ShapeKind::Circle | ShapeKind::Arc if self.radius.is_none() => {
+                        return Err(format!("{}: circles and arcs need a radius", what));
+                    }
+                    _ => {}
+                }
+                if shape != ShapeKind::Arc {
+                    unused(self.start.is_some(), "start")?;
+                    unused(self.end.is_some(), "end")?;
+                } else if self.start.is_none() || self.end.is_none() {
+                    return Err(format!("{}: arcs need a start and an end", what));
+                }
+                if shape == ShapeKind::Line && self.fill.is_some() {
+                    return Err(format!("{}: lines have no inside to fill", what));
+                }
+
+                // without a fill there has to be an outline to see anything
+                let stroke = self
+                    .stroke
+                    .unwrap_or(if self.fill.is_some() { 0.0 } else { 1.0 });
+                if stroke < 0.0 {
+                    return Err(format!("{}: stroke can't be negative", what));
+                }
+                if stroke == 0.0 && (shape == ShapeKind::Line || self.fill.is_none()) {
+                    return Err(format!("{}: needs a stroke or a fill", what));
+                }
+                if stroke == 0.0 && (self.color.is_some() || self.dash.is_some()) {
+                    return Err(format!("{}: color and dash are of the stroke", what));
+                }
+                let dash = self.dash.unwrap_or_default();
+                if dash.iter().any(|d| *d <= 0.0) {
+                    return Err(format!("{}: dashes and gaps must be longer than 0", what));
+                }
+                let color = Color::gray(self.color.unwrap_or(0));
+                Content::Shape {
+                    shape,
+                    points,
+                    width: self.width,
+                    height: self.height,
+                    radius: self.radius.unwrap_or(0),
+                    start: self.start.unwrap_or(0.0),
+                    end: self.end.unwrap_or(0.0),
+                    style: Style {
+                        fill: self.fill.map(Color::gray),
+                        stroke: (stroke > 0.0).then(|| Stroke::dashed(stroke, color, dash)),
+                    },
+                }
+            }
+        };
+
+        Ok(Widget {
+            name: self.name,
+            label: what,
+            when: self.when,
+            place,
+            content,
+        })
+    }
+}
+
+/* what widgets are made from on a draw */
+struct Context<'a> {
+    registry: &'a FontRegistry,
+    config: &'a Config,
+    values: Values<'a>,
+    // of fonts and icons
+    scale: f32,
+    // a widget that doesn't fit is an error, otherwise it is cut off at the region
+    strict: bool,
+}
+
+/* the room a widget is laid out in: lengths in percent are of `parent`, "fill" takes all of
+ * `available`, which is what is left from where the widget starts
+ */
+#[derive(Debug, Clone, Copy)]
+struct Room {
+    parent: Size,
+    available: Size,
+}
+
+impl Room {
+    fn width(&self, length: Length) -> u32 {
+        length.within(self.parent.width, self.available.width)
+    }
+
+    fn height(&self, length: Length) -> u32 {
+        length.within(self.parent.height, self.available.height)
+    }
+}
+
+impl Widget {
+    /* creates the objects of the widget in `region` and returns where they went. a widget that
+     * isn't shown takes no room, widgets placed next to it move up to where it would be
+     */
+    fn add_to(
+        &self,
+        region: &mut Region,
+        cx: &Context,
+        named: &HashMap<String, Rect>,
+    ) -> Result<Rect, Error> {
+        let margin = cx.config.layout.margin;
+        let (x, y) = self
+            .place
+            .origin(region.width, region.height, margin, named);
+        let empty = Rect {
+            x,
+            y,
+            width: 0,
+            height: 0,
+        };
+        if x < 0 || y < 0 || x >= region.width as i64 || y >= region.height as i64 {
+            if !cx.strict {
+                return Ok(empty);
+            }
+            return Err(Error::Config(ConfigError::Invalid(format!(
+                "{} starts outside of its region at {},{}",
+                self.label, x, y
+            ))));
+        }
+
+        let pos = Point::new(x as u32, y as u32);
+        let room = Room {
+            parent: Size::new(region.width, region.height),
+            available: Size::new(region.width, region.height)
+                .shrink(pos.x + margin, pos.y + margin),
+        };
+        let Some(mut object) = self.build(room, cx)? else {
+            return Ok(empty);
+        };
+        let size = object.measure(room.available);
+        let fits = pos.x + size.width <= region.width && pos.y + size.height <= region.height;
+        if cx.strict && !fits {
+            return Err(Error::Config(ConfigError::Invalid(format!(
+                "{} of {}x{} at {},{} doesn't fit into its region of {}x{}",
+                self.label, size.width, size.height, x, y, region.width, region.height
+            ))));
+        }
+        object.arrange(pos, size);
+        region.add_object(object);
+        Ok(Rect {
+            x,
+            y,
+            width: size.width as i64,
+            height: size.height as i64,
+        })
+    }
+
+    /* the objects of the widget at the origin, ready to be measured and arranged. None if the
+     * widget isn't shown
+     */
+    fn build(&self, room: Room, cx: &Context) -> Result<Option<Box<dyn Drawable>>, Error> {
+        if !self.when.matches(cx.values.snapshot.stale) {
+            return Ok(None);
+        }
+        let config = cx.config;
+        let origin = Point::new(0, 0);
+
+        let object: Box<dyn Drawable> = match &self.content {
+            Content::Text {
+                template,
+                font,
+                width,
+                height,
+                wrap,
+                align,
+                valign,
+                line_spacing,
+                overflow,
+            } => {
+                let Some(text) = template.render(&cx.values) else {
+                    return Ok(None);
+                };
+                let font = font.setting(config, cx.scale);
+                match width {
+                    Some(width) => {
+                        let bounds = TextBox {
+                            width: room.width(*width),
+                            height: height.map(|h| room.height(h)),
+                            wrap: *wrap,
+                            align: *align,
+                            valign: *valign,
+                            line_spacing: *line_spacing,
+                            overflow: *overflow,
+                        };
+                        Text::layout(cx.registry, &text, origin, font, &bounds)?
+                    }
+                    None => Text::new(cx.registry, &text, origin, font)?,
+                }
+            }
+            Content::Icon { file, scale } => {
+                let path = match file {
+                    Some(file) => config.layout.icons.join(file),
+                    None => {
+                        let current = &cx.values.snapshot.report.current;
+                        let condition = current.weather.first().cloned().unwrap_or_default();
+                        icons::icon_path(&config.layout.icons, &condition)
+                    }
+                };
+                Image::new(path, origin, scale * cx.scale)?
+            }
+            Content::Forecast {
+                font,
+                days,
+                scale,
+                width,
+            } => {
+                let forecast = cx.values.snapshot.report.forecast.daily(&config.time);
+                // the API sometimes returns a partial day at the end
+                let forecast = &forecast[..forecast.len().min(*days)];
+                let gap = self.place.gap;
+                let mut grid = Grid::even(forecast.len(), gap);
+                grid.width = width.map(|w| room.width(w));
+                let widths = grid.column_widths(grid.width.unwrap_or(room.available.width));
+                let day_width = widths.first().copied().unwrap_or(0);
+                // the lines get smaller rather than wider than their day
+                let bounds = TextBox {
+                    overflow: Overflow::Shrink,
+                    ..TextBox::line(day_width)
+                };
+                let font = font.setting(config, cx.scale);
+                let text = |s: &str| Text::layout(cx.registry, s, origin, font.clone(), &bounds);
+                let scale = scale * cx.scale;
+
+                for day in forecast {
+                    let mut column = Stack::vertical(gap);
+                    column.add(text(&config.time.date(day.date, "%a"))?);
+                    let icon_path = icons::icon_path(&config.layout.icons, &day.weather);
+                    let mut icon = Image::new(&icon_path, origin, scale)?;
+                    if icon.width > day_width {
+                        let scale = scale * day_width as f32 / icon.width as f32;
+                        icon = Image::new(&icon_path, origin, scale)?;
+                    }
+                    column.add(icon);
+                    column.add(text(&format!("{:.0}/{:.0}", day.temp_max, day.temp_min))?);
+                    column.add(text(&format!("{:.1}mm", day.precipitation))?);
+                    column.add(text(&format!("{:.0}%", day.pop * 100.0))?);
+                    grid.add(Box::new(column));
+                }
+                Box::new(grid)
+            }
+            Content::List {
+                font,
+                rows,
+                icon_width,
+                width,
+            } => {
+                let width = width.map(|w| room.width(w));
+                let gap = self.place.gap;
+                let icon_width = (*icon_width as f32 * cx.scale).round() as u32;
+                let text_width = width
+                    .unwrap_or(room.available.width)
+                    .saturating_sub(icon_width + gap);
+                // the text is centered next to the icon
+                let mut grid = Grid::new(vec![Track::Px(icon_width), Track::Weight(1.0)], gap);
+                grid.width = width;
+                grid.valign = Anchor::Center;
+                let mut height = 0;
+                let mut shown = 0;
+                for row in rows {
+                    let Some(line) = row.text.render(&cx.values) else {
+                        continue;
+                    };
+                    let bounds = TextBox::line(text_width);
+                    let font = font.setting(config, cx.scale);
+                    let text = Text::layout(cx.registry, &line, origin, font, &bounds)?;
+                    let icon: Box<dyn Drawable> = match &row.icon {
+                        Some(file) => Image::new(config.layout.icons.join(file), origin, cx.scale)?,
+                        None => Spacer::new(0, 0),
+                    };
+                    let row_height = icon.measure(room.available).height.max(text.height);
+                    // rows that don't fit are left out, the region was made too small for them
+                    let top = if height == 0 { 0 } else { height + gap };
+                    if top + row_height > room.available.height {
+                        if !cx.strict {
+                            break;
+                        }
+                        let total = rows
+                            .iter()
+                            .filter(|row| row.text.render(&cx.values).is_some())
+                            .count();
+                        return Err(Error::Config(ConfigError::Invalid(format!(
+                            "{} has room for {} of its {} rows",
+                            self.label, shown, total
+                        ))));
+                    }
+                    height = top + row_height;
+                    shown += 1;
+                    grid.add(icon);
+                    grid.add(text);
+                }
+                Box::new(grid)
+            }
+            Content::Container {
+                arrangement,
+                padding,
+                border,
+                anchor,
+                width,
+                children,
+            } => {
+                let width = width.map(|w| room.width(w));
+                let outer = Size::new(width.unwrap_or(room.available.width), room.available.height);
+                let inner = outer.shrink(padding * 2, padding * 2);
+                let room = Room {
+                    parent: inner,
+                    available: inner,
+                };
+                let gap = self.place.gap;
+                // children of a grid are laid out in the width of their column
+                let columns = match arrangement {
+                    Arrangement::Grid(columns) => Grid::new(columns.clone(), gap)
+                        .column_widths(inner.width)
+                        .into_iter()
+                        .map(|width| Room {
+                            parent: Size::new(width, inner.height),
+                            available: Size::new(width, inner.height),
+                        })
+                        .collect(),
+                    _ => vec![room],
+                };
+                let mut objects = Vec::new();
+                for child in children {
+                    let room = columns[objects.len() % columns.len()];
+                    objects.extend(child.build(room, cx)?);
+                }
+
+                let mut container: Box<dyn Drawable> = match arrangement {
+                    Arrangement::Row | Arrangement::Column => {
+                        let mut stack = match arrangement {
+                            Arrangement::Row => Stack::horizontal(gap),
+                            _ => Stack::vertical(gap),
+                        };
+                        stack.padding = Padding::all(*padding);
+                        stack.anchor = *anchor;
+                        objects.into_iter().for_each(|o| stack.add(o));
+                        Box::new(stack)
+                    }
+                    Arrangement::Grid(columns) => {
+                        let mut grid = Grid::new(columns.clone(), gap);
+                        grid.width = width;
+                        grid.padding = Padding::all(*padding);
+                        grid.valign = *anchor;
+                        objects.into_iter().for_each(|o| grid.add(o));
+                        Box::new(grid)
+                    }
+                };
+                if *border {
+                    // a region of its own, the container is laid out inside of it
+                    let size = container.measure(outer);
+                    container.arrange(origin, size);
+                    let mut frame = Region::new(origin, size.width, size.height, true);
+                    frame.add_object(container);
+                    container = Box::new(frame);
+                }
+                container
+            }
+            Content::Shape {
+                shape,
+                points,
+                width,
+                height,
+                radius,
+                start,
+                end,
+                style,
+            } => {
+                let radius = *radius;
+                let figure = match shape {
+                    ShapeKind::Line => Figure::Line(points.clone()),
+                    ShapeKind::Rect => Figure::Rect {
+                        width: width.map_or(0, |w| room.width(w)),
+                        height: height.map_or(0, |h| room.height(h)),
+                        radius,
+                    },
+                    ShapeKind::Circle => Figure::Circle { radius },
+                    ShapeKind::Arc => Figure::Arc {
+                        radius,
+                        start: *start,
+                        end: *end,
+                    },
+                    ShapeKind::Polygon => Figure::Polygon(points.clone()),
+                };
+                Shape::new(origin, figure, style.clone())
+            }
+        };
+        Ok(Some(object))
+    }
+}
+
+fn default_gap() -> u32 {
+    10
+}
+
+fn default_border() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::MemoryDisplay;
+    use crate::framebuffer::MODELS;
+    use crate::screen::Rotation;
+
+    fn load(layout: &str, width: u32, height: u32) -> Result<Dashboard, Error> {
+        let config =
+            Config::parse("api_key = \"key\"\n[location]\ncity = \"Hildesheim\"\n").unwrap();
+        let registry = FontRegistry::default();
+        Dashboard::parse(
+            Path::new("test.toml"),
+            layout,
+            width,
+            height,
+            &config,
+            &registry,
+        )
+    }
+
+    #[test]
+    fn checks_widgets_when_loading() {
+        let layout = "[[region]]\nname = \"a\"\nheight = 60\n\
+                      [[region.widget]]\ntype = \"text\"\ntext = \"{description}\"\nfont = \"big\"\n";
+        let err = load(layout, 400, 300).unwrap_err().to_string();
+        assert!(err.contains("layout test.toml: region a, widget 1 of "));
+        assert!(err.contains("doesn't fit into its region of 380x60"));
+
+        // only the stale note doesn't fit
+        let layout = "[[region]]\nname = \"a\"\n\
+                      [[region.widget]]\ntype = \"text\"\ntext = \"here\"\n\
+                      [[region.widget]]\ntype = \"text\"\nwhen = \"stale\"\ny = 300\ntext = \"stale\"\n";
+        let err = load(layout, 400, 300).unwrap_err().to_string();
+        assert!(err.contains("widget 2 starts outside of its region"));
+
+        // three rows of the sample in a region with room for one
+        let layout = "[[region]]\nname = \"a\"\nheight = 80\n\
+                      [[region.widget]]\ntype = \"list\"\nfont = \"small\"\n\
+                      [[region.widget.row]]\ntext = \"{humidity}%\"\n\
+                      [[region.widget.row]]\ntext = \"{wind}\"\n\
+                      [[region.widget.row]]\ntext = \"{pressure}\"\n";
+        let err = load(layout, 400, 300).unwrap_err().to_string();
+        assert!(err.contains("region a, widget 1 has room for 1 of its 3 rows"));
+    }
+
+    #[test]
+    fn built_in_layouts_fit_every_model() {
+        let config =
+            Config::parse("api_key = \"key\"\n[location]\ncity = \"Hildesheim\"\n").unwrap();
+        let registry = FontRegistry::default();
+        let snapshot = Snapshot {
+            report: serde_json::from_str(SAMPLE).unwrap(),
+            fetched_at: 0,
+            stale: true,
+        };
+        for model in &MODELS {
+            for (width, height) in [(model.width, model.height), (model.height, model.width)] {
+                let dashboard = Dashboard::load(None, width, height, &config, &registry)
+                    .unwrap_or_else(|e| panic!("{} at {}x{}: {}", model.name, width, height, e));
+                let screen = Screen::with_display(MemoryDisplay::new(width, height), Rotation::R0);
+                dashboard
+                    .draw(&screen, &registry, &config, &snapshot)
+                    .unwrap_or_else(|e| panic!("{} at {}x{}: {}", model.name, width, height, e));
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_out_sun_times_in_polar_night() {
+        let config = Config::parse("api_key = \"key\"\n[location]\ncity = \"Tromsø\"\n").unwrap();
+        let forecast = include_str!("../fixtures/forecast_new_york.json");
+        let snapshot = |current: &str| Snapshot {
+            report: WeatherReport {
+                current: serde_json::from_str(current).unwrap(),
+                forecast: serde_json::from_str(forecast).unwrap(),
+            },
+            fetched_at: 0,
+            stale: false,
+        };
+        let sunrise = Template::try_from("sunrise {sunrise}".to_string()).unwrap();
+        let sunset = Template::try_from("sunset {sunset}".to_string()).unwrap();
+
+        let tromso = snapshot(include_str!("../fixtures/weather_tromso.json"));
+        let values = Values {
+            config: &config,
+            snapshot: &tromso,
+        };
+        assert_eq!(sunrise.render(&values), None);
+        assert_eq!(sunset.render(&values), None);
+        let humidity = Template::try_from("{humidity}%".to_string()).unwrap();
+        assert!(humidity.render(&values).is_some());
+
+        let new_york = snapshot(include_str!("../fixtures/weather_new_york.json"));
+        let values = Values {
+            config: &config,
+            snapshot: &new_york,
+        };
+        assert!(sunrise.render(&values).is_some());
+        assert!(sunset.render(&values).is_some());
+    }
+
+    #[test]
+    fn scales_to_the_reference() {
+        let layout = "reference = [800, 1200]\n[[region]]\nname = \"a\"\n";
+        assert_eq!(load(layout, 400, 900).unwrap().scale, 0.5);
+        assert_eq!(load(layout, 1600, 1800).unwrap().scale, 1.5);
+        let layout = "[[region]]\nname = \"a\"\n";
+        assert_eq!(load(layout, 400, 900).unwrap().scale, 1.0);
+
+        let layout = "reference = [800, 0]\n[[region]]\nname = \"a\"\n";
+        let err = load(layout, 400, 900).unwrap_err().to_string();
+        assert!(err.contains("the reference screen of 800x0 has no room"));
+    }
+}
@@ -0,0 +1,142 @@
+use std::io;
+use std::path::Path;
+
+use image::{ImageResult, RgbImage};
+
+use crate::framebuffer::{Mode, MxcfbRect};
+use crate::util::Point;
+
+/* Everything the screen needs from a piece of hardware (or something pretending to be one).
+ * The mxcfb framebuffer of the Kobo is the real thing, the MemoryDisplay below lets us render
+ * layouts on any machine.
+ */
+pub trait Display {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    fn set_pixel(&mut self, p: Point, rgb: [u8; 3]);
+
+    /* copies a grayscale picture into `rect`. `pixels` starts at the top left corner of the rect
+     * and has `stride` values per row
+     */
+    fn blit_gray(&mut self, rect: &MxcfbRect, pixels: &[u8], stride: usize) {
+        for y in 0..rect.height {
+            for x in 0..rect.width {
+                let g = pixels[y as usize * stride + x as usize];
+                self.set_pixel(Point::new(rect.left + x, rect.top + y), [g, g, g]);
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, rect: &MxcfbRect, gray: u8) {
+        for y in rect.top..rect.top + rect.height {
+            for x in rect.left..rect.left + rect.width {
+                self.set_pixel(Point::new(x, y), [gray, gray, gray]);
+            }
+        }
+    }
+
+    /* refresh the given rectangle of the panel. returns the update marker to wait for */
+    fn update(&mut self, rect: MxcfbRect, mode: Mode) -> io::Result<u32>;
+    /* block until the update with `marker` is visible */
+    fn wait(&mut self, marker: u32) -> io::Result<()>;
+
+    fn update_and_wait(&mut self, rect: MxcfbRect, mode: Mode) -> io::Result<()> {
+        let marker = self.update(rect, mode)?;
+        self.wait(marker)
+    }
+
+    /* block until all of several in-flight updates are visible. the panel works on them in
+     * parallel, so waiting only for the last one isn't enough when they don't overlap.
+     */
+    fn wait_all(&mut self, markers: &[u32]) -> io::Result<()> {
+        for marker in markers {
+            self.wait(*marker)?;
+        }
+        Ok(())
+    }
+}
+
+/* Display backed by a plain RGB buffer. updates don't do anything but count, in tests they are
+ * recorded. the current frame can be written out as PNG at any time.
+ */
+pub struct MemoryDisplay {
+    frame: RgbImage,
+    token: u32,
+    // every update that was asked for, in order
+    #[cfg(test)]
+    pub updates: Vec<(MxcfbRect, Mode)>,
+}
+
+impl MemoryDisplay {
+    pub fn new(width: u32, height: u32) -> MemoryDisplay {
+        MemoryDisplay {
+            frame: RgbImage::new(width, height),
+            token: 1,
+            #[cfg(test)]
+            updates: Vec::new(),
+        }
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.frame.save_with_format(path, image::ImageFormat::Png)
+    }
+
+    #[cfg(test)]
+    pub fn pixel(&self, p: Point) -> [u8; 3] {
+        self.frame.get_pixel(p.x, p.y).0
+    }
+}
+
+impl Display for MemoryDisplay {
+    fn width(&self) -> u32 {
+        self.frame.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.frame.height()
+    }
+
+    fn set_pixel(&mut self, p: Point, rgb: [u8; 3]) {
+        assert!(p.x < self.frame.width() && p.y < self.frame.height());
+        self.frame.put_pixel(p.x, p.y, image::Rgb(rgb));
+    }
+
+    fn blit_gray(&mut self, rect: &MxcfbRect, pixels: &[u8], stride: usize) {
+        assert!(rect.left + rect.width <= self.frame.width());
+        assert!(rect.top + rect.height <= self.frame.height());
+        let width = self.frame.width() as usize;
+        let buffer: &mut [u8] = &mut self.frame;
+        for y in 0..rect.height as usize {
+            let src = &pixels[y * stride..y * stride + rect.width as usize];
+            let start = ((rect.top as usize + y) * width + rect.left as usize) * 3;
+            let dst = &mut buffer[start..start + src.len() * 3];
+            for (rgb, g) in dst.chunks_exact_mut(3).zip(src) {
+                rgb.fill(*g);
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, rect: &MxcfbRect, gray: u8) {
+        assert!(rect.left + rect.width <= self.frame.width());
+        assert!(rect.top + rect.height <= self.frame.height());
+        let width = self.frame.width() as usize;
+        let buffer: &mut [u8] = &mut self.frame;
+        for y in rect.top as usize..(rect.top + rect.height) as usize {
+            let start = (y * width + rect.left as usize) * 3;
+            buffer[start..start + rect.width as usize * 3].fill(gray);
+        }
+    }
+
+    #[cfg_attr(not(test), allow(unused_variables))]
+    fn update(&mut self, rect: MxcfbRect, mode: Mode) -> io::Result<u32> {
+        #[cfg(test)]
+        self.updates.push((rect, mode));
+        let update_marker = self.token;
+        self.token = self.token.wrapping_add(1).max(1);
+        Ok(update_marker)
+    }
+
+    fn wait(&mut self, _marker: u32) -> io::Result<()> {
+        Ok(())
+    }
+}
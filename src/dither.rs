@@ -0,0 +1,211 @@
+use serde::Deserialize;
+
+use crate::util::Point;
+
+/* gray levels a waveform mode can show. anything in between is rounded by the controller, which
+ * turns smooth gradients into visible bands, so we quantize ourselves and dither the error away.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Levels {
+    Sixteen,
+    Four,
+    Two,
+}
+
+impl Levels {
+    fn count(&self) -> i32 {
+        match self {
+            Levels::Sixteen => 16,
+            Levels::Four => 4,
+            Levels::Two => 2,
+        }
+    }
+
+    // the level closest to `v`, as 8 bit gray
+    pub fn quantize(&self, v: i32) -> u8 {
+        let steps = self.count() - 1;
+        let level = (v.clamp(0, 255) * steps + 127) / 255;
+        (level * 255 / steps) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    // plain rounding to the nearest level
+    None,
+    // spreads all of the error, smoothest gradients
+    #[default]
+    FloydSteinberg,
+    // spreads 3/4 of the error, more contrast and less noise in 1 bit
+    Atkinson,
+    // 4x4 Bayer matrix, a regular pattern that doesn't crawl between partial updates
+    Ordered,
+}
+
+// (dx, dy, weight) of the neighbours that get part of the error, and the sum of the weights
+const FLOYD_STEINBERG: (&[(i32, i32, i32)], i32) =
+    (&[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)], 16);
+const ATKINSON: (&[(i32, i32, i32)], i32) = (
+    &[
+        (1, 0, 1),
+        (2, 0, 1),
+        (-1, 1, 1),
+        (0, 1, 1),
+        (1, 1, 1),
+        (0, 2, 1),
+    ],
+    8,
+);
+
+const BAYER: [[i32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/* reduces `pixels`, rows of `width` 8 bit grays, to `levels`. `pos` is where the pixels are on the
+ * screen, so the ordered pattern lines up between neighbouring pictures.
+ */
+pub fn dither(pixels: &mut [u8], width: usize, pos: Point, levels: Levels, method: Dither) {
+    match method {
+        Dither::None => {
+            for px in pixels.iter_mut() {
+                *px = levels.quantize(*px as i32);
+            }
+        }
+        Dither::Ordered => {
+            for (i, px) in pixels.iter_mut().enumerate() {
+                let x = pos.x + (i % width) as u32;
+                let y = pos.y + (i / width) as u32;
+                *px = ordered(*px, Point::new(x, y), levels);
+            }
+        }
+        Dither::FloydSteinberg => diffuse(pixels, width, levels, FLOYD_STEINBERG),
+        Dither::Atkinson => diffuse(pixels, width, levels, ATKINSON),
+    }
+}
+
+fn ordered(v: u8, pos: Point, levels: Levels) -> u8 {
+    let step = 255 / (levels.count() - 1);
+    let threshold = BAYER[pos.y as usize % 4][pos.x as usize % 4];
+    // offset in -step/2..step/2
+    let offset = (threshold * 2 + 1 - 16) * step / 32;
+    levels.quantize(v as i32 + offset)
+}
+
+fn diffuse(
+    pixels: &mut [u8],
+    width: usize,
+    levels: Levels,
+    (kernel, total): (&[(i32, i32, i32)], i32),
+) {
+    if width == 0 {
+        return;
+    }
+    let height = pixels.len() / width;
+    // values with the error received so far, which can leave 0..=255
+    let mut values: Vec<i32> = pixels.iter().map(|px| *px as i32).collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let new = levels.quantize(values[i]);
+            let error = values[i] - new as i32;
+            pixels[i] = new;
+
+            for (dx, dy, weight) in kernel {
+                let (nx, ny) = (x as i32 + dx, y + *dy as usize);
+                if nx < 0 || nx as usize >= width || ny >= height {
+                    continue;
+                }
+                values[ny * width + nx as usize] += error * weight / total;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVELS: [Levels; 3] = [Levels::Two, Levels::Four, Levels::Sixteen];
+    const METHODS: [Dither; 4] = [
+        Dither::None,
+        Dither::FloydSteinberg,
+        Dither::Atkinson,
+        Dither::Ordered,
+    ];
+
+    // the grays the waveform shows for `levels`
+    fn allowed(levels: Levels) -> Vec<u8> {
+        let step = 255 / (levels.count() - 1);
+        (0..levels.count()).map(|i| (i * step) as u8).collect()
+    }
+
+    #[test]
+    fn quantizes_to_the_waveform_levels() {
+        assert_eq!(allowed(Levels::Two), [0, 255]);
+        assert_eq!(allowed(Levels::Four), [0, 85, 170, 255]);
+        assert_eq!(allowed(Levels::Sixteen).len(), 16);
+        for levels in LEVELS {
+            let mut seen: Vec<u8> = (-20..280).map(|v| levels.quantize(v)).collect();
+            seen.dedup();
+            assert_eq!(seen, allowed(levels), "{:?}", levels);
+            // every level stays what it is
+            assert!(allowed(levels)
+                .iter()
+                .all(|v| levels.quantize(*v as i32) == *v));
+        }
+        assert_eq!(Levels::Two.quantize(127), 0);
+        assert_eq!(Levels::Two.quantize(128), 255);
+        assert_eq!(Levels::Four.quantize(42), 0);
+        assert_eq!(Levels::Four.quantize(43), 85);
+    }
+
+    #[test]
+    fn dithers_to_allowed_levels_only() {
+        // a gradient from black to white over every row
+        let gradient: Vec<u8> = (0..32 * 8).map(|i| (i % 32 * 8) as u8).collect();
+        for levels in LEVELS {
+            for method in METHODS {
+                let mut pixels = gradient.clone();
+                dither(&mut pixels, 32, Point::new(3, 5), levels, method);
+                let allowed = allowed(levels);
+                assert!(
+                    pixels.iter().all(|v| allowed.contains(v)),
+                    "{:?} {:?}",
+                    levels,
+                    method
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_brightness_of_flat_areas() {
+        let mean = |levels: Levels, method: Dither, gray: u8| {
+            let mut pixels = vec![gray; 32 * 32];
+            dither(&mut pixels, 32, Point::new(0, 0), levels, method);
+            pixels.iter().map(|v| *v as u32).sum::<u32>() / pixels.len() as u32
+        };
+        for method in [Dither::FloydSteinberg, Dither::Atkinson, Dither::Ordered] {
+            for (levels, gray) in [(Levels::Two, 0x80), (Levels::Four, 0x60)] {
+                let mean = mean(levels, method, gray);
+                assert!(
+                    mean.abs_diff(gray as u32) < 16,
+                    "{} in {:?} {:?}",
+                    mean,
+                    levels,
+                    method
+                );
+            }
+        }
+        // further from the middle too, Atkinson drops part of the error for contrast and doesn't
+        for method in [Dither::FloydSteinberg, Dither::Ordered] {
+            let mean = mean(Levels::Two, method, 0x40);
+            assert!(mean.abs_diff(0x40) < 16, "{} in {:?}", mean, method);
+        }
+
+        // plain rounding loses it
+        let mut pixels = vec![0x40; 16];
+        dither(&mut pixels, 4, Point::new(0, 0), Levels::Two, Dither::None);
+        assert!(pixels.iter().all(|v| *v == 0));
+    }
+}
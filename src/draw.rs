@@ -0,0 +1,796 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::error::Error;
+use crate::gui::Drawable;
+use crate::layout::Size;
+use crate::screen::Canvas;
+use crate::util::{Color, Point};
+
+/* Vector shapes with antialiased edges: lines, rectangles with rounded corners, circles, arcs and
+ * polygons, filled and/or outlined with a stroke that may be dashed.
+ * Every shape is turned into polygons first, curves into many short edges. the polygons of a fill
+ * or a stroke are filled together, a pixel is blended with how much of it they cover.
+ */
+
+// rows of a pixel that are sampled, along a row the coverage is exact
+const SUBROWS: u32 = 4;
+// corners sharper than this ratio of miter length to stroke width are cut off
+const MITER_LIMIT: f32 = 4.0;
+// length of the edges curves are made of
+const CURVE_STEP: f32 = 1.5;
+
+/* how lines and outlines are drawn */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub color: Color,
+    // lengths of the dashes and the gaps between them in turn, a solid line if empty
+    pub dash: Vec<f32>,
+}
+
+impl Stroke {
+    pub fn solid(width: f32, color: Color) -> Stroke {
+        Stroke {
+            width,
+            color,
+            dash: Vec::new(),
+        }
+    }
+
+    pub fn dashed(width: f32, color: Color, dash: Vec<f32>) -> Stroke {
+        Stroke { width, color, dash }
+    }
+}
+
+/* how a shape is painted: inside, along its outline, or both */
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Style {
+    pub fill: Option<Color>,
+    pub stroke: Option<Stroke>,
+}
+
+impl Style {
+    pub fn outlined(stroke: Stroke) -> Style {
+        Style {
+            fill: None,
+            stroke: Some(stroke),
+        }
+    }
+}
+
+/* Shapes on anything that can be drawn on, a region as well as the screen. positions are pixels,
+ * lines run through the middle of them. what is outside of the canvas is left out
+ */
+pub trait Draw: Canvas {
+    /* lines from point to point, joined at the corners. they cover the pixels at both ends */
+    fn line(&self, points: &[Point], stroke: &Stroke) -> Result<(), Error> {
+        let path: Vec<Vec2> = points.iter().map(|p| Vec2::center(*p)).collect();
+        draw_stroke(self, &path, false, true, stroke)
+    }
+
+    /* the rectangle of `width`x`height` at `pos` with corners rounded by `radius`. the outline is
+     * inside of it, a border of one pixel takes the outermost pixels
+     */
+    fn rect(
+        &self,
+        pos: Point,
+        width: u32,
+        height: u32,
+        radius: u32,
+        style: &Style,
+    ) -> Result<(), Error> {
+        let (x, y) = (pos.x as f32, pos.y as f32);
+        let (w, h) = (width as f32, height as f32);
+        let outline = |inset: f32| {
+            rounded_rect(
+                Vec2::new(x + inset, y + inset),
+                Vec2::new(x + w - inset, y + h - inset),
+                radius as f32 - inset,
+            )
+        };
+        paint(self, outline, style)
+    }
+
+    /* the circle around `center`, the outline is inside of it */
+    fn circle(&self, center: Point, radius: u32, style: &Style) -> Result<(), Error> {
+        let c = Vec2::center(center);
+        let outline = |inset: f32| arc_points(c, radius as f32 - inset, 0.0, 360.0, true);
+        paint(self, outline, style)
+    }
+
+    /* the part of the circle around `center` from `start` to `end` degrees, clockwise from the
+     * top like a compass. filled it is a slice of the circle, the stroke only follows the arc
+     */
+    fn arc(
+        &self,
+        center: Point,
+        radius: u32,
+        start: f32,
+        end: f32,
+        style: &Style,
+    ) -> Result<(), Error> {
+        let c = Vec2::center(center);
+        if let Some(color) = style.fill {
+            let mut slice = vec![c];
+            slice.extend(arc_points(c, radius as f32, start, end, false));
+            fill(self, &[slice], color)?;
+        }
+        if let Some(stroke) = &style.stroke {
+            let path = arc_points(c, radius as f32 - stroke.width / 2.0, start, end, false);
+            draw_stroke(self, &path, false, false, stroke)?;
+        }
+        Ok(())
+    }
+
+    /* the polygon with corners at `points`, the outline is centered on its edges */
+    fn polygon(&self, points: &[Point], style: &Style) -> Result<(), Error> {
+        let path: Vec<Vec2> = points.iter().map(|p| Vec2::center(*p)).collect();
+        paint(self, |_| path.clone(), style)
+    }
+}
+
+impl<C: Canvas + ?Sized> Draw for C {}
+
+/* what a Shape draws, relative to its position */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Figure {
+    // lines through the points, only stroked
+    Line(Vec<Point>),
+    Rect { width: u32, height: u32, radius: u32 },
+    // in a square of twice the radius
+    Circle { radius: u32 },
+    Arc { radius: u32, start: f32, end: f32 },
+    Polygon(Vec<Point>),
+}
+
+/* a figure as an object of a region */
+pub struct Shape {
+    pub pos: Point,
+    pub figure: Figure,
+    pub style: Style,
+}
+
+impl Shape {
+    pub fn new(pos: Point, figure: Figure, style: Style) -> Box<Shape> {
+        Box::new(Shape { pos, figure, style })
+    }
+
+    fn offset(&self, points: &[Point]) -> Vec<Point> {
+        points.iter().map(|p| self.pos + *p).collect()
+    }
+}
+
+impl Drawable for Shape {
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        match &self.figure {
+            Figure::Line(points) => match &self.style.stroke {
+                Some(stroke) => canvas.line(&self.offset(points), stroke),
+                None => Ok(()),
+            },
+            Figure::Rect {
+                width,
+                height,
+                radius,
+            } => canvas.rect(self.pos, *width, *height, *radius, &self.style),
+            Figure::Circle { radius } => {
+                let center = self.pos + Point::new(*radius, *radius);
+                canvas.circle(center, *radius, &self.style)
+            }
+            Figure::Arc { radius, start, end } => {
+                let center = self.pos + Point::new(*radius, *radius);
+                canvas.arc(center, *radius, *start, *end, &self.style)
+            }
+            Figure::Polygon(points) => canvas.polygon(&self.offset(points), &self.style),
+        }
+    }
+
+    fn measure(&self, _available: Size) -> Size {
+        match &self.figure {
+            Figure::Line(points) | Figure::Polygon(points) => {
+                // the stroke reaches past the last pixel by half its width
+                let reach = self
+                    .style
+                    .stroke
+                    .as_ref()
+                    .map_or(0, |s| (s.width / 2.0).ceil() as u32);
+                let width = points.iter().map(|p| p.x).max().unwrap_or(0);
+                let height = points.iter().map(|p| p.y).max().unwrap_or(0);
+                Size::new(width + 1 + reach, height + 1 + reach)
+            }
+            Figure::Rect { width, height, .. } => Size::new(*width, *height),
+            Figure::Circle { radius } | Figure::Arc { radius, .. } => {
+                Size::new(radius * 2 + 1, radius * 2 + 1)
+            }
+        }
+    }
+
+    // a rectangle takes the size it is given, e.g. to stretch across a column
+    fn arrange(&mut self, pos: Point, size: Size) {
+        self.pos = pos;
+        if let Figure::Rect { width, height, .. } = &mut self.figure {
+            *width = size.width;
+            *height = size.height;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vec2 {
+    x: f32,
+    y: f32,
+}
+
+impl Vec2 {
+    fn new(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+
+    // the middle of a pixel
+    fn center(p: Point) -> Vec2 {
+        Vec2::new(p.x as f32 + 0.5, p.y as f32 + 0.5)
+    }
+
+    fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    fn normalized(self) -> Vec2 {
+        self * (1.0 / self.length())
+    }
+
+    // turned by 90 degrees
+    fn perp(self) -> Vec2 {
+        Vec2::new(-self.y, self.x)
+    }
+
+    fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    fn cross(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Vec2) -> Vec2 {
+        Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Vec2) -> Vec2 {
+        Vec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: f32) -> Vec2 {
+        Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+/* fills and strokes a closed shape. `outline` gives its outline moved inwards by some pixels,
+ * the stroke goes along the one moved by half its width so it stays inside
+ */
+fn paint<C: Canvas + ?Sized>(
+    canvas: &C,
+    outline: impl Fn(f32) -> Vec<Vec2>,
+    style: &Style,
+) -> Result<(), Error> {
+    if let Some(color) = style.fill {
+        fill(canvas, &[outline(0.0)], color)?;
+    }
+    if let Some(stroke) = &style.stroke {
+        draw_stroke(canvas, &outline(stroke.width / 2.0), true, false, stroke)?;
+    }
+    Ok(())
+}
+
+/* points on the circle around `c` from `start` to `end` degrees, clockwise from the top. a full
+ * circle that is `closed` leaves out the last point, which is the first one again
+ */
+fn arc_points(c: Vec2, radius: f32, start: f32, end: f32, closed: bool) -> Vec<Vec2> {
+    if radius <= 0.0 {
+        return vec![c];
+    }
+    let (start, sweep) = (start.to_radians(), (end - start).to_radians());
+    let steps = ((sweep.abs() * radius / CURVE_STEP).ceil() as usize).clamp(1, 1024);
+    let last = if closed { steps - 1 } else { steps };
+    (0..=last)
+        .map(|i| {
+            let a = start + sweep * i as f32 / steps as f32;
+            Vec2::new(c.x + radius * a.sin(), c.y - radius * a.cos())
+        })
+        .collect()
+}
+
+/* outline of the rectangle from `min` to `max`, clockwise from the top left corner */
+fn rounded_rect(min: Vec2, max: Vec2, radius: f32) -> Vec<Vec2> {
+    let radius = radius.min((max.x - min.x) / 2.0).min((max.y - min.y) / 2.0);
+    if radius <= 0.0 {
+        return vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+    }
+    let corners = [
+        (Vec2::new(max.x - radius, min.y + radius), 0.0),
+        (Vec2::new(max.x - radius, max.y - radius), 90.0),
+        (Vec2::new(min.x + radius, max.y - radius), 180.0),
+        (Vec2::new(min.x + radius, min.y + radius), 270.0),
+    ];
+    corners
+        .iter()
+        .flat_map(|(c, start)| arc_points(*c, radius, *start, start + 90.0, false))
+        .collect()
+}
+
+/* draws `stroke` along `path`. open paths get square ends that reach half the width past their
+ * last points if `caps` is set, otherwise they end right there. dashes always end right there
+ */
+fn draw_stroke<C: Canvas + ?Sized>(
+    canvas: &C,
+    path: &[Vec2],
+    closed: bool,
+    caps: bool,
+    stroke: &Stroke,
+) -> Result<(), Error> {
+    if stroke.width <= 0.0 || path.is_empty() {
+        return Ok(());
+    }
+    let half = stroke.width / 2.0;
+    let mut polygons = Vec::new();
+    if stroke.dash.iter().sum::<f32>() > 0.0 {
+        for dash in dashes(path, closed, &stroke.dash) {
+            polygons.extend(outline(&dash, false, false, half));
+        }
+    } else {
+        polygons = outline(path, closed, caps, half);
+    }
+    fill(canvas, &polygons, stroke.color)
+}
+
+/* the pieces of `path` where the dashes are */
+fn dashes(path: &[Vec2], closed: bool, pattern: &[f32]) -> Vec<Vec<Vec2>> {
+    let mut points = path.to_vec();
+    if closed {
+        points.push(path[0]);
+    }
+    let mut pieces = Vec::new();
+    let mut current = vec![points[0]];
+    let (mut i, mut on) = (0, true);
+    let mut left = pattern[0];
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let length = (b - a).length();
+        let mut done = 0.0;
+        while length - done > left {
+            done += left;
+            let p = a + (b - a) * (done / length);
+            if on {
+                current.push(p);
+                pieces.push(std::mem::take(&mut current));
+            } else {
+                current = vec![p];
+            }
+            on = !on;
+            i = (i + 1) % pattern.len();
+            left = pattern[i];
+        }
+        left -= length - done;
+        if on {
+            current.push(b);
+        }
+    }
+    if on && current.len() > 1 {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/* polygons that cover a stroke of `half` the width along `path`: one for every edge and one
+ * for every corner to fill the gap between the edges
+ */
+fn outline(path: &[Vec2], closed: bool, caps: bool, half: f32) -> Vec<Vec<Vec2>> {
+    let mut points: Vec<Vec2> = Vec::with_capacity(path.len());
+    for p in path {
+        if points.last().is_none_or(|last| (*p - *last).length() > 1e-3) {
+            points.push(*p);
+        }
+    }
+    if closed && points.len() > 2 && (points[0] - points[points.len() - 1]).length() <= 1e-3 {
+        points.pop();
+    }
+    if points.len() == 1 {
+        // a line of no length is a dot as big as the stroke is wide
+        let p = points[0];
+        return match caps {
+            true => vec![rounded_rect(
+                p - Vec2::new(half, half),
+                p + Vec2::new(half, half),
+                0.0,
+            )],
+            false => Vec::new(),
+        };
+    }
+
+    let edges = if closed {
+        points.len()
+    } else {
+        points.len() - 1
+    };
+    let mut polygons = Vec::new();
+    for i in 0..edges {
+        let (mut a, mut b) = (points[i], points[(i + 1) % points.len()]);
+        let d = (b - a).normalized();
+        if !closed && caps {
+            if i == 0 {
+                a = a - d * half;
+            }
+            if i == edges - 1 {
+                b = b + d * half;
+            }
+        }
+        let n = d.perp() * half;
+        polygons.push(vec![a + n, b + n, b - n, a - n]);
+    }
+
+    let corners = if closed { 0..points.len() } else { 1..points.len() - 1 };
+    for i in corners {
+        let before = points[(i + points.len() - 1) % points.len()];
+        let (v, after) = (points[i], points[(i + 1) % points.len()]);
+        let (d1, d2) = ((v - before).normalized(), (after - v).normalized());
+        let turn = d1.cross(d2);
+        if turn.abs() < 1e-6 {
+            continue;
+        }
+        // the gap is on the outer side of the turn
+        let side = -turn.signum() * half;
+        let (n1, n2) = (d1.perp() * side, d2.perp() * side);
+        let middle = n1 + n2;
+        if middle.length() < 1e-6 {
+            continue;
+        }
+        let u = middle.normalized();
+        let cos = u.dot(n1) / half;
+        if 1.0 / cos > MITER_LIMIT {
+            polygons.push(vec![v, v + n1, v + n2]);
+        } else {
+            polygons.push(vec![v, v + n1, v + u * (half / cos), v + n2]);
+        }
+    }
+
+    // all the same way around, so they add up where they overlap instead of cancelling out
+    for polygon in &mut polygons {
+        if area(polygon) < 0.0 {
+            polygon.reverse();
+        }
+    }
+    polygons
+}
+
+// signed, positive if the points go clockwise on the screen
+fn area(polygon: &[Vec2]) -> f32 {
+    let next = polygon.iter().cycle().skip(1);
+    polygon.iter().zip(next).map(|(a, b)| a.cross(*b)).sum::<f32>() / 2.0
+}
+
+/* fills the area that `polygons` wind around with `color`. they are filled as one, pixels where
+ * they overlap are only painted once
+ */
+fn fill<C: Canvas + ?Sized>(canvas: &C, polygons: &[Vec<Vec2>], color: Color) -> Result<(), Error> {
+    // the non-horizontal edges as (top, bottom, direction)
+    let mut edges = Vec::new();
+    for polygon in polygons {
+        let next = polygon.iter().cycle().skip(1);
+        for (a, b) in polygon.iter().zip(next) {
+            if a.y < b.y {
+                edges.push((*a, *b, 1));
+            } else if a.y > b.y {
+                edges.push((*b, *a, -1));
+            }
+        }
+    }
+    if edges.is_empty() {
+        return Ok(());
+    }
+
+    let clamp = |v: f32, max: u32| (v.max(0.0) as u32).min(max);
+    let left = clamp(
+        edges.iter().map(|e| e.0.x.min(e.1.x)).fold(f32::MAX, f32::min).floor(),
+        canvas.width(),
+    );
+    let right = clamp(
+        edges.iter().map(|e| e.0.x.max(e.1.x)).fold(f32::MIN, f32::max).ceil(),
+        canvas.width(),
+    );
+    let top = clamp(
+        edges.iter().map(|e| e.0.y).fold(f32::MAX, f32::min).floor(),
+        canvas.height(),
+    );
+    let bottom = clamp(
+        edges.iter().map(|e| e.1.y).fold(f32::MIN, f32::max).ceil(),
+        canvas.height(),
+    );
+    if left >= right || top >= bottom {
+        return Ok(());
+    }
+
+    let mut coverage = vec![0.0f32; (right - left) as usize];
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    let share = 1.0 / SUBROWS as f32;
+    for py in top..bottom {
+        let mut touched: Option<(usize, usize)> = None;
+        for s in 0..SUBROWS {
+            let y = py as f32 + (s as f32 + 0.5) * share;
+            crossings.clear();
+            for (a, b, dir) in &edges {
+                if y >= a.y && y < b.y {
+                    let x = a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y);
+                    crossings.push((x - left as f32, *dir));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let (mut winding, mut start) = (0, 0.0);
+            for (x, dir) in &crossings {
+                if winding == 0 {
+                    start = *x;
+                }
+                winding += dir;
+                if winding == 0 {
+                    if let Some(span) = cover(&mut coverage, start, *x, share) {
+                        touched = Some(match touched {
+                            None => span,
+                            Some((from, to)) => (from.min(span.0), to.max(span.1)),
+                        });
+                    }
+                }
+            }
+        }
+
+        let Some((from, to)) = touched else {
+            continue;
+        };
+        let alpha = |c: f32| (c.min(1.0) * 255.0).round() as u8;
+        let mut x = from;
+        while x <= to {
+            // pixels that are covered all the way are painted as runs
+            let start = x;
+            while x <= to && alpha(coverage[x]) == 255 {
+                x += 1;
+            }
+            if x > start {
+                let pos = Point::new(left + start as u32, py);
+                canvas.fill_rect(pos, (x - start) as u32, 1, color)?;
+                continue;
+            }
+            if alpha(coverage[x]) > 0 {
+                canvas.blend(Point::new(left + x as u32, py), color, alpha(coverage[x]))?;
+            }
+            x += 1;
+        }
+        coverage[from..=to].fill(0.0);
+    }
+    Ok(())
+}
+
+/* adds `share` times how much of each pixel the span from `start` to `end` covers, returns the
+ * first and last pixel it reached
+ */
+fn cover(coverage: &mut [f32], start: f32, end: f32, share: f32) -> Option<(usize, usize)> {
+    let (start, end) = (start.max(0.0), end.min(coverage.len() as f32));
+    if start >= end {
+        return None;
+    }
+    let (first, last) = (start as usize, (end.ceil() as usize - 1).max(start as usize));
+    if first == last {
+        coverage[first] += (end - start) * share;
+        return Some((first, last));
+    }
+    coverage[first] += (first as f32 + 1.0 - start) * share;
+    for c in &mut coverage[first + 1..last] {
+        *c += share;
+    }
+    coverage[last] += (end - last as f32) * share;
+    Some((first, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::MemoryDisplay;
+    use crate::dither::Dither;
+    use crate::screen::{Rotation, Screen};
+
+    /* the grays of a screen of `width`x`height` after `draw`, rows of columns */
+    fn render(
+        width: u32,
+        height: u32,
+        draw: impl FnOnce(&Screen<MemoryDisplay>) -> Result<(), Error>,
+    ) -> Vec<Vec<u8>> {
+        let screen = Screen::with_display(MemoryDisplay::new(width, height), Rotation::R0);
+        screen.set_dither(Dither::None);
+        draw(&screen).unwrap();
+        screen.update().unwrap();
+        let fb = screen.fb.borrow();
+        (0..height)
+            .map(|y| (0..width).map(|x| fb.pixel(Point::new(x, y))[0]).collect())
+            .collect()
+    }
+
+    // (x, y) of the pixels that were painted at all
+    fn painted(pixels: &[Vec<u8>]) -> Vec<(usize, usize)> {
+        let mut painted = Vec::new();
+        for (y, row) in pixels.iter().enumerate() {
+            for (x, v) in row.iter().enumerate() {
+                if *v < 255 {
+                    painted.push((x, y));
+                }
+            }
+        }
+        painted
+    }
+
+    #[test]
+    fn draws_straight_lines_on_pixels() {
+        let stroke = Stroke::solid(1.0, Color::gray(0));
+        let pixels = render(16, 16, |s| {
+            s.line(&[Point::new(2, 5), Point::new(12, 5)], &stroke)
+        });
+        assert_eq!(
+            painted(&pixels),
+            (2..=12).map(|x| (x, 5)).collect::<Vec<_>>()
+        );
+        assert!(pixels[5][2..=12].iter().all(|v| *v == 0));
+
+        let pixels = render(16, 16, |s| {
+            s.line(&[Point::new(7, 1), Point::new(7, 14)], &stroke)
+        });
+        assert_eq!(
+            painted(&pixels),
+            (1..=14).map(|y| (7, y)).collect::<Vec<_>>()
+        );
+        assert!((1..=14).all(|y| pixels[y][7] == 0));
+    }
+
+    #[test]
+    fn covers_diagonals_partly() {
+        let stroke = Stroke::solid(1.0, Color::gray(0));
+        let pixels = render(16, 16, |s| {
+            s.line(&[Point::new(0, 0), Point::new(15, 15)], &stroke)
+        });
+        // on the line it is dark, next to it the edges are antialiased
+        assert!(pixels[7][7] < 128);
+        assert!((0..15).any(|i| pixels[i][i + 1] > 0 && pixels[i][i + 1] < 255));
+        assert_eq!(pixels[15][0], 255);
+        assert_eq!(pixels[0][15], 255);
+    }
+
+    #[test]
+    fn joins_corners_with_miters() {
+        let stroke = Stroke::solid(3.0, Color::gray(0));
+        let corner = [Point::new(3, 3), Point::new(12, 3), Point::new(12, 12)];
+        let pixels = render(16, 16, |s| s.line(&corner, &stroke));
+        // the outer corner of the turn is filled, not notched
+        assert_eq!(pixels[2][13], 0);
+        assert_eq!(pixels[2][2], 0);
+        assert_eq!(pixels[13][13], 0);
+        assert_eq!(pixels[8][8], 255);
+    }
+
+    #[test]
+    fn rounds_rectangle_corners() {
+        let style = Style {
+            fill: Some(Color::gray(0)),
+            stroke: None,
+        };
+        let pixels = render(16, 16, |s| s.rect(Point::new(0, 0), 16, 16, 6, &style));
+        for (x, y) in [(0, 0), (15, 0), (0, 15), (15, 15)] {
+            assert_eq!(pixels[y][x], 255, "corner {},{}", x, y);
+        }
+        for (x, y) in [(8, 0), (0, 8), (15, 8), (8, 15), (8, 8)] {
+            assert_eq!(pixels[y][x], 0, "edge {},{}", x, y);
+        }
+
+        // the outline stays inside and leaves the middle alone
+        let style = Style::outlined(Stroke::solid(1.0, Color::gray(0)));
+        let pixels = render(16, 16, |s| s.rect(Point::new(0, 0), 16, 16, 0, &style));
+        assert_eq!(pixels[0][0], 0);
+        assert_eq!(pixels[15][8], 0);
+        assert_eq!(pixels[8][8], 255);
+    }
+
+    #[test]
+    fn fills_circles_and_arcs() {
+        let style = Style {
+            fill: Some(Color::gray(0)),
+            stroke: None,
+        };
+        let pixels = render(21, 21, |s| s.circle(Point::new(10, 10), 5, &style));
+        let painted = painted(&pixels);
+        let xs = painted.iter().map(|p| p.0);
+        let ys = painted.iter().map(|p| p.1);
+        assert_eq!((xs.clone().min(), xs.max()), (Some(5), Some(15)));
+        assert_eq!((ys.clone().min(), ys.max()), (Some(5), Some(15)));
+        assert_eq!(pixels[10][10], 0);
+
+        // a quarter from the top to the right
+        let pixels = render(21, 21, |s| s.arc(Point::new(10, 10), 5, 0.0, 90.0, &style));
+        assert_eq!(pixels[8][12], 0);
+        assert_eq!(pixels[8][8], 255);
+        assert_eq!(pixels[12][8], 255);
+        assert_eq!(pixels[12][12], 255);
+    }
+
+    #[test]
+    fn fills_concave_polygons() {
+        let style = Style {
+            fill: Some(Color::gray(0)),
+            stroke: None,
+        };
+        // an upside down U, open at the bottom
+        let points = [
+            (0, 0),
+            (12, 0),
+            (12, 12),
+            (8, 12),
+            (8, 4),
+            (4, 4),
+            (4, 12),
+            (0, 12),
+        ];
+        let points: Vec<Point> = points.iter().map(|(x, y)| Point::new(*x, *y)).collect();
+        let pixels = render(16, 16, |s| s.polygon(&points, &style));
+        assert_eq!(pixels[2][6], 0);
+        assert_eq!(pixels[8][2], 0);
+        assert_eq!(pixels[8][10], 0);
+        assert_eq!(pixels[8][6], 255);
+        assert_eq!(pixels[14][2], 255);
+    }
+
+    #[test]
+    fn leaves_gaps_between_dashes() {
+        let stroke = Stroke::dashed(1.0, Color::gray(0), vec![4.0, 4.0]);
+        let pixels = render(20, 8, |s| {
+            s.line(&[Point::new(0, 5), Point::new(19, 5)], &stroke)
+        });
+        // dashes from 0.5 to 4.5 and 8.5 to 12.5, gaps in between
+        for x in [1, 2, 3, 9, 10, 11, 17, 18] {
+            assert_eq!(pixels[5][x], 0, "dash at {}", x);
+        }
+        for x in [5, 6, 7, 13, 14, 15] {
+            assert_eq!(pixels[5][x], 255, "gap at {}", x);
+        }
+        assert!(pixels
+            .iter()
+            .enumerate()
+            .all(|(y, r)| y == 5 || r.iter().all(|v| *v == 255)));
+    }
+
+    #[test]
+    fn cuts_off_shapes_at_the_edge() {
+        let fill = Style {
+            fill: Some(Color::gray(0)),
+            stroke: Some(Stroke::dashed(2.0, Color::gray(0), vec![3.0, 1.0])),
+        };
+        let pixels = render(16, 16, |s| {
+            s.circle(Point::new(2, 2), 10, &fill)?;
+            s.rect(Point::new(10, 10), 20, 20, 4, &fill)?;
+            s.arc(Point::new(15, 0), 30, 90.0, 270.0, &fill)?;
+            s.line(
+                &[Point::new(0, 15), Point::new(100, 15)],
+                &Stroke::solid(3.0, Color::gray(0)),
+            )?;
+            s.polygon(
+                &[Point::new(8, 8), Point::new(40, 8), Point::new(8, 40)],
+                &fill,
+            )
+        });
+        assert_eq!(pixels[0][0], 0);
+        assert_eq!(pixels[15][15], 0);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::ConfigError;
+use crate::openweather::WeatherError;
+
+/* Everything that can stop us from showing the weather. Errors are rendered onto the display
+ * before we give up or retry, an eink panel would otherwise keep showing the old, valid looking
+ * image.
+ */
+#[derive(Debug)]
+pub enum Error {
+    // bad command line, printed along with the usage
+    Usage(String),
+    Config(ConfigError),
+    // opening, mapping or refreshing the display
+    Display(io::Error),
+    // setting up the handlers that stop the daemon
+    Signal(io::Error),
+    Weather(WeatherError),
+    Image(PathBuf, image::ImageError),
+    // an image drawn where the canvas doesn't have room for it
+    ImagePlacement(PathBuf, String),
+    Font(PathBuf, String),
+    // drawing outside of the screen and the like
+    Render(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(e) => write!(f, "{}", e),
+            Error::Config(e) => write!(f, "{}", e),
+            Error::Display(e) => write!(f, "display: {}", e),
+            Error::Signal(e) => write!(f, "cannot handle signals: {}", e),
+            Error::Weather(e) => write!(f, "weather: {}", e),
+            Error::Image(path, e) => write!(f, "image {}: {}", path.display(), e),
+            Error::ImagePlacement(path, e) => write!(f, "image {}: {}", path.display(), e),
+            Error::Font(path, e) => write!(f, "font {}: {}", path.display(), e),
+            Error::Render(e) => write!(f, "render: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<WeatherError> for Error {
+    fn from(e: WeatherError) -> Self {
+        Error::Weather(e)
+    }
+}
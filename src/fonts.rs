@@ -0,0 +1,206 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rusttype::{Font, GlyphId, PositionedGlyph, Scale, VMetrics};
+
+use crate::error::Error;
+
+// fonts that are built into the binary, so text shows up even without a fonts directory
+const EMBEDDED: [(&str, &[u8]); 1] = [(
+    "LucidaTypewriterRegular.ttf",
+    include_bytes!("../fonts/LucidaTypewriterRegular.ttf"),
+)];
+
+// the font that is always there
+pub const DEFAULT_FONT: &str = EMBEDDED[0].0;
+
+/* Loads every font once and hands out the same instance to all texts, cloning a `Font` only
+ * clones a reference to the parsed data.
+ * Fonts are looked up by file name in the configured search paths, then in the fonts directory
+ * next to the executable, and last among the embedded ones.
+ */
+pub struct FontRegistry {
+    paths: Vec<PathBuf>,
+    // tried in order for characters the requested font doesn't have
+    fallbacks: Vec<String>,
+    loaded: RefCell<HashMap<String, Font<'static>>>,
+    // fallbacks that failed to load, they are reported once and not tried again
+    missing: RefCell<HashSet<String>>,
+}
+
+impl FontRegistry {
+    pub fn new(paths: &[PathBuf], fallbacks: &[String]) -> FontRegistry {
+        let mut paths = paths.to_vec();
+        // the working directory is wherever the init script started us, the executable is not
+        if let Some(dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("fonts")))
+        {
+            paths.push(dir);
+        }
+
+        FontRegistry {
+            paths,
+            fallbacks: fallbacks.to_vec(),
+            loaded: RefCell::new(HashMap::new()),
+            missing: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /* the font `name` followed by the fallbacks. a broken fallback only costs us the characters
+     * it would have had, so it is left out instead of failing every text
+     */
+    pub fn chain(&self, name: &str) -> Result<FontChain, Error> {
+        let mut fonts = vec![self.get(name)?];
+        for fallback in &self.fallbacks {
+            if self.missing.borrow().contains(fallback) {
+                continue;
+            }
+            match self.get(fallback) {
+                Ok(font) => fonts.push(font),
+                Err(e) => {
+                    eprintln!("skipping fallback font: {}", e);
+                    self.missing.borrow_mut().insert(fallback.clone());
+                }
+            }
+        }
+        Ok(FontChain { fonts })
+    }
+
+    fn get(&self, name: &str) -> Result<Font<'static>, Error> {
+        if let Some(font) = self.loaded.borrow().get(name) {
+            return Ok(font.clone());
+        }
+
+        let font = self.load(name)?;
+        self.loaded
+            .borrow_mut()
+            .insert(name.to_string(), font.clone());
+        Ok(font)
+    }
+
+    fn load(&self, name: &str) -> Result<Font<'static>, Error> {
+        for dir in &self.paths {
+            let path = dir.join(name);
+            match std::fs::read(&path) {
+                Ok(data) => {
+                    return Font::try_from_vec(data)
+                        .ok_or_else(|| Error::Font(path, "not a TrueType font".to_string()))
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::Font(path, e.to_string())),
+            }
+        }
+
+        EMBEDDED
+            .iter()
+            .find(|(embedded, _)| *embedded == name)
+            .and_then(|(_, data)| Font::try_from_bytes(data))
+            .ok_or_else(|| {
+                let paths: Vec<_> = self.paths.iter().map(|p| p.display().to_string()).collect();
+                Error::Font(
+                    Path::new(name).to_owned(),
+                    format!("not found in {}", paths.join(", ")),
+                )
+            })
+    }
+}
+
+impl Default for FontRegistry {
+    fn default() -> Self {
+        FontRegistry::new(&[], &[])
+    }
+}
+
+/* A font and its fallbacks, laid out as if they were one. every character comes from the first
+ * font that has a glyph for it, the vertical metrics are those of the first font.
+ */
+#[derive(Clone)]
+pub struct FontChain {
+    fonts: Vec<Font<'static>>,
+}
+
+impl FontChain {
+    pub fn v_metrics(&self, scale: Scale) -> VMetrics {
+        self.fonts[0].v_metrics(scale)
+    }
+
+    // index of the font to take `c` from, the first one shows its "missing" glyph if none has it
+    fn pick(&self, c: char) -> usize {
+        self.fonts
+            .iter()
+            .position(|font| font.glyph(c).id() != GlyphId(0))
+            .unwrap_or(0)
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.fonts
+            .iter()
+            .any(|font| font.glyph(c).id() != GlyphId(0))
+    }
+
+    /* one line of text with its first pen position at `start`. pairs of glyphs from the same
+     * font are kerned, a change of font starts over
+     */
+    pub fn layout(
+        &self,
+        text: &str,
+        scale: Scale,
+        start: rusttype::Point<f32>,
+    ) -> Vec<PositionedGlyph<'static>> {
+        let mut glyphs = Vec::new();
+        let mut caret = start.x;
+        let mut last: Option<(usize, GlyphId)> = None;
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let i = self.pick(c);
+            let font = &self.fonts[i];
+            let glyph = font.glyph(c).scaled(scale);
+            if let Some((last_font, last_id)) = last {
+                if last_font == i {
+                    caret += font.pair_kerning(scale, last_id, glyph.id());
+                }
+            }
+            last = Some((i, glyph.id()));
+
+            let advance = glyph.h_metrics().advance_width;
+            glyphs.push(glyph.positioned(rusttype::point(caret, start.y)));
+            caret += advance;
+        }
+        glyphs
+    }
+
+    /* width of `text` in pixels, from the first pen position to past the last glyph. this is the
+     * advance and not the ink, so spaces count and glyphs without outline don't get in the way
+     */
+    pub fn measure(&self, text: &str, scale: Scale) -> f32 {
+        self.layout(text, scale, rusttype::point(0.0, 0.0))
+            .last()
+            .map_or(0.0, |g| {
+                g.position().x + g.unpositioned().h_metrics().advance_width
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_missing_fallbacks() {
+        let fallbacks = ["Missing.ttf".to_string(), DEFAULT_FONT.to_string()];
+        let registry = FontRegistry::new(&[], &fallbacks);
+        let chain = registry.chain(DEFAULT_FONT).unwrap();
+        assert_eq!(chain.fonts.len(), 2);
+        assert!(chain.has_glyph('a'));
+        assert!(registry.missing.borrow().contains("Missing.ttf"));
+        // and is not looked for again
+        assert_eq!(registry.chain(DEFAULT_FONT).unwrap().fonts.len(), 2);
+
+        assert!(matches!(
+            registry.chain("Missing.ttf"),
+            Err(Error::Font(path, _)) if path == Path::new("Missing.ttf")
+        ));
+    }
+}
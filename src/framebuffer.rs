@@ -1,5 +1,7 @@
 extern crate libc;
-use crate::util::Point;
+use crate::display::Display;
+use crate::dither::Levels;
+use crate::util::{luma, Point};
 use libc::ioctl;
 
 use std::{
@@ -38,22 +40,40 @@ const EPDC_FLAG_ENABLE_INVERSION: libc::c_uint = 0x01;
 #[allow(unused)]
 const EPDC_FLAG_FORCE_MONOCHROME: libc::c_uint = 0x02;
 
-// these ioctl IDs seem to be specific for this platform (Kobo Aura One)
-// usually you find the IDs 0x2E and 0x2F for these ioctls but here we have some custom version of
-// these?
-// Someone on the internet reverse engineered this, I just stole it
+// Kobo patched the mxcfb driver, so the ioctl IDs differ from the usual 0x2E and 0x2F ones.
+// _IOW('F', 0x2E, struct mxcfb_update_data) with the NTX variant of the struct, up to Mark 6
+// (e.g. Glo, Aura One). Someone on the internet reverse engineered this, I just stole it
 const MXCFB_SEND_UPDATE: libc::c_ulong = 0x4044462E;
+// _IOW('F', 0x2E, struct mxcfb_update_data) of the Mark 7 kernels (e.g. Clara HD, Libra H2O)
+const MXCFB_SEND_UPDATE_V2: libc::c_ulong = 0x4048462E;
+// _IOW('F', 0x2F, __u32), the older kernels only take the marker
 const MXCFB_WAIT_FOR_UPDATE_COMPLETE: libc::c_ulong = 0x4004462F;
+// _IOWR('F', 0x2F, struct mxcfb_update_marker_data), Mark 7
+const MXCFB_WAIT_FOR_UPDATE_COMPLETE_V2: libc::c_ulong = 0xC008462F;
 
 #[allow(unused)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Mode {
+    // black and white only, the quickest update without flashing
     Fast,
+    // 4 gray levels, quicker than the 16 of the others
+    Gray4,
     Partial,
     Gui,
     Full,
 }
 
+impl Mode {
+    // what the waveform of this mode can show
+    pub fn levels(&self) -> Levels {
+        match self {
+            Mode::Fast => Levels::Two,
+            Mode::Gray4 => Levels::Four,
+            Mode::Partial | Mode::Gui | Mode::Full => Levels::Sixteen,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Debug)]
 pub struct FixScreenInfo {
@@ -125,6 +145,17 @@ pub struct MxcfbRect {
     pub height: u32,
 }
 
+impl MxcfbRect {
+    fn empty() -> MxcfbRect {
+        MxcfbRect {
+            top: 0,
+            left: 0,
+            width: 0,
+            height: 0,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Debug)]
 struct MxcfbAltBufferData {
@@ -147,6 +178,224 @@ struct MxcfbUpdateData {
     alt_buffer_data: MxcfbAltBufferData,
 }
 
+// the alt buffer of the Mark 7 kernels lost its virtual address
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct MxcfbAltBufferDataV2 {
+    phys_addr: u32,
+    width: u32,
+    height: u32,
+    alt_update_region: MxcfbRect,
+}
+
+// argument of MXCFB_SEND_UPDATE_V2
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct MxcfbUpdateDataV2 {
+    update_region: MxcfbRect,
+    waveform_mode: u32,
+    update_mode: u32,
+    update_marker: u32,
+    temp: libc::c_int,
+    flags: libc::c_uint,
+    dither_mode: libc::c_int,
+    quant_bit: libc::c_int,
+    alt_buffer_data: MxcfbAltBufferDataV2,
+}
+
+// argument of MXCFB_WAIT_FOR_UPDATE_COMPLETE_V2
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct MxcfbUpdateMarkerData {
+    update_marker: u32,
+    // set by the kernel if the update collided with another one
+    collision_test: u32,
+}
+
+// which of the two wait ioctls the kernel understands
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WaitIoctl {
+    Marker,
+    MarkerData,
+}
+
+// which update struct the kernel expects with MXCFB_SEND_UPDATE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UpdateData {
+    Ntx,
+    V2,
+}
+
+/* What differs between the Kobo models we know of. Every generation of the EPDC driver comes with
+ * its own structs, ioctl IDs and waveform numbers.
+ */
+#[derive(Debug)]
+pub struct Model {
+    pub name: &'static str,
+    // what /bin/kobo_config.sh prints, the init scripts export it as $PRODUCT
+    pub product: &'static str,
+    update_data: UpdateData,
+    wait_ioctl: WaitIoctl,
+    // waveform modes of Mode::Fast and Mode::Gray4, the Mark 7 kernels renumbered them
+    waveform_a2: u32,
+    waveform_gray4: u32,
+    // clockwise rotation that puts the picture upright on the framebuffer the device boots with,
+    // in degrees. the kernel's own rotation (var_info.rotate) is already part of xres/yres
+    pub rotation: u32,
+    // of the panel held upright, in pixels
+    #[allow(unused)]
+    pub width: u32,
+    #[allow(unused)]
+    pub height: u32,
+}
+
+pub const MODELS: [Model; 4] = [
+    Model {
+        name: "glo",
+        product: "kraken",
+        update_data: UpdateData::Ntx,
+        wait_ioctl: WaitIoctl::Marker,
+        waveform_a2: WaveformMode::A2 as u32,
+        waveform_gray4: WaveformMode::Gc4 as u32,
+        rotation: 90,
+        width: 758,
+        height: 1024,
+    },
+    Model {
+        name: "aura-one",
+        product: "daylight",
+        update_data: UpdateData::Ntx,
+        wait_ioctl: WaitIoctl::Marker,
+        waveform_a2: WaveformMode::A2 as u32,
+        waveform_gray4: WaveformMode::Gc4 as u32,
+        rotation: 0,
+        width: 1404,
+        height: 1872,
+    },
+    Model {
+        name: "clara-hd",
+        product: "nova",
+        update_data: UpdateData::V2,
+        wait_ioctl: WaitIoctl::MarkerData,
+        waveform_a2: 6,
+        // DU4, 3 is GL16 there
+        waveform_gray4: 7,
+        rotation: 0,
+        width: 1072,
+        height: 1448,
+    },
+    Model {
+        name: "libra-h2o",
+        product: "storm",
+        update_data: UpdateData::V2,
+        wait_ioctl: WaitIoctl::MarkerData,
+        waveform_a2: 6,
+        // DU4, 3 is GL16 there
+        waveform_gray4: 7,
+        rotation: 0,
+        width: 1264,
+        height: 1680,
+    },
+];
+
+// the device this program was written on
+const DEFAULT_MODEL: &str = "aura-one";
+
+impl Model {
+    // by our name for it or by the product name of the firmware
+    pub fn by_name(name: &str) -> Option<&'static Model> {
+        MODELS.iter().find(|d| d.name == name || d.product == name)
+    }
+
+    /* asks the firmware what we are running on, like the Kobo init scripts do */
+    pub fn detect() -> &'static Model {
+        let product = std::env::var("PRODUCT").ok().or_else(|| {
+            let output = std::process::Command::new("/bin/kobo_config.sh")
+                .output()
+                .ok()?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        });
+
+        match product.as_deref().and_then(Model::by_name) {
+            Some(model) => model,
+            None => {
+                eprintln!(
+                    "unknown model {}, assuming {}",
+                    product.as_deref().unwrap_or("(no $PRODUCT)"),
+                    DEFAULT_MODEL
+                );
+                Model::by_name(DEFAULT_MODEL).unwrap()
+            }
+        }
+    }
+}
+
+/* how a pixel is laid out in the mapped memory */
+#[derive(Clone, Debug)]
+pub enum PixelFormat {
+    // one byte of luminance
+    Gray8,
+    // 2 (RGB565) or 4 (XRGB8888) bytes, the channels are where the bitfields say
+    Rgb {
+        bytes: usize,
+        red: Bitfield,
+        green: Bitfield,
+        blue: Bitfield,
+        transp: Bitfield,
+    },
+}
+
+impl PixelFormat {
+    fn detect(info: &VarScreenInfo) -> io::Result<PixelFormat> {
+        match info.bits_per_pixel {
+            8 => Ok(PixelFormat::Gray8),
+            16 | 32 => Ok(PixelFormat::Rgb {
+                bytes: info.bits_per_pixel as usize / 8,
+                red: info.red.clone(),
+                green: info.green.clone(),
+                blue: info.blue.clone(),
+                transp: info.transp.clone(),
+            }),
+            bpp => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} bits per pixel are not supported", bpp),
+            )),
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            PixelFormat::Gray8 => 1,
+            PixelFormat::Rgb { bytes, .. } => *bytes,
+        }
+    }
+
+    // the bytes of a pixel as they go into memory, only the first bytes() are used
+    fn pack(&self, rgb: [u8; 3]) -> [u8; 4] {
+        let value = match self {
+            PixelFormat::Gray8 => luma(rgb) as u32,
+            PixelFormat::Rgb {
+                red,
+                green,
+                blue,
+                transp,
+                ..
+            } => pack(rgb[0], red) | pack(rgb[1], green) | pack(rgb[2], blue) | pack(255, transp),
+        };
+        // the framebuffer is in native byte order, little endian on every Kobo
+        value.to_ne_bytes()
+    }
+}
+
+// puts the upper `length` bits of a channel where the bitfield says
+fn pack(value: u8, field: &Bitfield) -> u32 {
+    if field.length == 0 {
+        return 0;
+    }
+    let length = field.length.min(8);
+    ((value as u32) >> (8 - length)) << field.offset
+}
+
 impl ::std::default::Default for Bitfield {
     fn default() -> Self {
         unsafe { ::std::mem::zeroed() }
@@ -170,7 +419,13 @@ pub struct Framebuffer {
     frame_size: libc::size_t,
     token: u32,
     flags: u32,
-    pub bytes_per_pixel: u8,
+    wait_ioctl: WaitIoctl,
+    pub model: &'static Model,
+    pub format: PixelFormat,
+    // bytes of every gray level in the pixel format, only the first format.bytes() are used
+    gray: Vec<[u8; 4]>,
+    // one packed row, reused by blit_gray and fill_rect
+    row: Vec<u8>,
     pub var_info: VarScreenInfo,
     pub fix_info: FixScreenInfo,
 }
@@ -194,18 +449,17 @@ pub fn var_screen_info(device: &File) -> io::Result<VarScreenInfo> {
 }
 
 impl Framebuffer {
-    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Framebuffer> {
+    pub fn new<P: AsRef<Path>>(path: P, model: &'static Model) -> io::Result<Framebuffer> {
         let device = OpenOptions::new().read(true).write(true).open(path)?;
 
         let var_info = var_screen_info(&device)?;
         let fix_info = fix_screen_info(&device)?;
 
-        assert_eq!(var_info.bits_per_pixel % 8, 0);
-
-        let bytes_per_pixel = var_info.bits_per_pixel / 8;
+        let format = PixelFormat::detect(&var_info)?;
 
-        let mut frame_size =
-            (var_info.xres_virtual * var_info.yres_virtual * bytes_per_pixel) as libc::size_t;
+        let mut frame_size = var_info.xres_virtual as libc::size_t
+            * var_info.yres_virtual as libc::size_t
+            * format.bytes();
 
         if frame_size > fix_info.smem_len as usize {
             frame_size = fix_info.smem_len as usize;
@@ -227,90 +481,292 @@ impl Framebuffer {
         if frame == libc::MAP_FAILED {
             Err(io::Error::last_os_error())
         } else {
+            let gray = (0..=255u8).map(|g| format.pack([g, g, g])).collect();
             Ok(Framebuffer {
                 device,
                 frame,
                 frame_size,
                 token: 1,
                 flags: 0,
-                bytes_per_pixel: bytes_per_pixel as u8,
+                wait_ioctl: model.wait_ioctl,
+                model,
+                format,
+                gray,
+                row: Vec::new(),
                 var_info,
                 fix_info,
             })
         }
     }
 
+    // offset of a pixel in the mapped memory
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (self.var_info.xoffset + x) as usize * self.format.bytes()
+            + (self.var_info.yoffset + y) as usize * self.fix_info.line_length as usize
+    }
+
     pub fn set_pixel(&mut self, p: Point, rgb: [u8; 3]) {
-        let addr = (self.var_info.xoffset as isize + p.x as isize)
-            * (self.bytes_per_pixel as isize)
-            + (self.var_info.yoffset as isize + p.y as isize)
-                * (self.fix_info.line_length as isize);
+        let bytes = self.format.bytes();
+        let addr = self.offset(p.x, p.y);
 
-        assert!(addr < self.frame_size as isize);
+        assert!(addr + bytes <= self.frame_size);
 
+        let value = self.format.pack(rgb);
         unsafe {
-            let pixel = self.frame.offset(addr) as *mut u8;
-            *pixel.offset(2) = rgb[0];
-            *pixel.offset(1) = rgb[1];
-            *pixel.offset(0) = rgb[2];
-            *pixel.offset(3) = 0;
+            let pixel = (self.frame as *mut u8).add(addr);
+            ptr::copy_nonoverlapping(value.as_ptr(), pixel, bytes);
+        }
+    }
+
+    // copies the packed pixels in self.row to each line of `rect`
+    fn copy_row(&mut self, rect: &MxcfbRect, y: u32) {
+        assert!(rect.left + rect.width <= self.var_info.xres);
+        assert!(y < self.var_info.yres);
+        let addr = self.offset(rect.left, y);
+        assert!(addr + self.row.len() <= self.frame_size);
+        unsafe {
+            let dst = (self.frame as *mut u8).add(addr);
+            ptr::copy_nonoverlapping(self.row.as_ptr(), dst, self.row.len());
+        }
+    }
+
+    /* copies a grayscale picture into `rect`. `pixels` starts at the top left corner of the rect
+     * and has `stride` values per row. every row is packed once and copied with a single memcpy,
+     * which is a lot faster than going through set_pixel for every pixel.
+     */
+    pub fn blit_gray(&mut self, rect: &MxcfbRect, pixels: &[u8], stride: usize) {
+        let bytes = self.format.bytes();
+        for y in 0..rect.height {
+            let start = y as usize * stride;
+            let line = &pixels[start..start + rect.width as usize];
+            self.row.clear();
+            for g in line {
+                self.row.extend_from_slice(&self.gray[*g as usize][..bytes]);
+            }
+            self.copy_row(rect, rect.top + y);
+        }
+    }
+
+    pub fn fill_rect(&mut self, rect: &MxcfbRect, gray: u8) {
+        let bytes = self.format.bytes();
+        self.row.clear();
+        for _ in 0..rect.width {
+            self.row
+                .extend_from_slice(&self.gray[gray as usize][..bytes]);
+        }
+        for y in rect.top..rect.top + rect.height {
+            self.copy_row(rect, y);
         }
     }
 
     pub fn update<T: Into<MxcfbRect>>(&mut self, rect: T, mode: Mode) -> io::Result<u32> {
         let (update_mode, waveform_mode) = match mode {
             Mode::Fast => (UpdateMode::Partial, WaveformMode::A2),
+            Mode::Gray4 => (UpdateMode::Partial, WaveformMode::Gc4),
             Mode::Partial => (UpdateMode::Partial, WaveformMode::Auto),
             Mode::Gui => (UpdateMode::Full, WaveformMode::Auto),
             Mode::Full => (UpdateMode::Full, WaveformMode::Gc16),
         };
-        let alt_buffer_data = MxcfbAltBufferData {
-            virt_addr: ptr::null(),
-            phys_addr: 0,
-            width: 0,
-            height: 0,
-            alt_update_region: MxcfbRect {
-                top: 0,
-                left: 0,
-                width: 0,
-                height: 0,
-            },
+        let waveform_mode = match waveform_mode {
+            WaveformMode::A2 => self.model.waveform_a2,
+            WaveformMode::Gc4 => self.model.waveform_gray4,
+            mode => mode as u32,
         };
-
+        let update_region: MxcfbRect = rect.into();
         let update_marker = self.token;
-        let update_data = MxcfbUpdateData {
-            update_region: rect.into(),
-            waveform_mode: waveform_mode as u32,
-            update_mode: update_mode as u32,
-            update_marker,
-            temp: TEMP_USE_AMBIENT,
-            flags: self.flags,
-            alt_buffer_data,
+        let fd = self.device.as_raw_fd();
+
+        let result = match self.model.update_data {
+            UpdateData::Ntx => {
+                let update_data = MxcfbUpdateData {
+                    update_region,
+                    waveform_mode,
+                    update_mode: update_mode as u32,
+                    update_marker,
+                    temp: TEMP_USE_AMBIENT,
+                    flags: self.flags,
+                    alt_buffer_data: MxcfbAltBufferData {
+                        virt_addr: ptr::null(),
+                        phys_addr: 0,
+                        width: 0,
+                        height: 0,
+                        alt_update_region: MxcfbRect::empty(),
+                    },
+                };
+                unsafe { libc::ioctl(fd, MXCFB_SEND_UPDATE, &update_data) }
+            }
+            UpdateData::V2 => {
+                let update_data = MxcfbUpdateDataV2 {
+                    update_region,
+                    waveform_mode,
+                    update_mode: update_mode as u32,
+                    update_marker,
+                    temp: TEMP_USE_AMBIENT,
+                    flags: self.flags,
+                    dither_mode: 0,
+                    quant_bit: 0,
+                    alt_buffer_data: MxcfbAltBufferDataV2 {
+                        phys_addr: 0,
+                        width: 0,
+                        height: 0,
+                        alt_update_region: MxcfbRect::empty(),
+                    },
+                };
+                unsafe { libc::ioctl(fd, MXCFB_SEND_UPDATE_V2, &update_data) }
+            }
         };
-        let result =
-            unsafe { libc::ioctl(self.device.as_raw_fd(), MXCFB_SEND_UPDATE, &update_data) };
         match result {
             -1 => Err(io::Error::last_os_error()),
             _ => {
-                self.token = self.token.wrapping_add(1);
+                // 0 means "no marker" to the driver
+                self.token = self.token.wrapping_add(1).max(1);
                 Ok(update_marker)
             }
         }
     }
 
-    pub fn wait(&mut self) -> io::Result<i32> {
-        // ??? token reqauired for ioctl, usage unclear
-        let token = 1;
-        let result = unsafe {
-            libc::ioctl(
-                self.device.as_raw_fd(),
-                MXCFB_WAIT_FOR_UPDATE_COMPLETE,
-                &token,
-            )
+    fn wait_ioctl(&self, wait_ioctl: WaitIoctl, marker: u32) -> io::Result<()> {
+        let fd = self.device.as_raw_fd();
+        let result = match wait_ioctl {
+            WaitIoctl::Marker => unsafe {
+                libc::ioctl(fd, MXCFB_WAIT_FOR_UPDATE_COMPLETE, &marker)
+            },
+            WaitIoctl::MarkerData => {
+                let mut data = MxcfbUpdateMarkerData {
+                    update_marker: marker,
+                    collision_test: 0,
+                };
+                unsafe { libc::ioctl(fd, MXCFB_WAIT_FOR_UPDATE_COMPLETE_V2, &mut data) }
+            }
         };
         match result {
             -1 => Err(io::Error::last_os_error()),
-            _ => Ok(result as i32),
+            _ => Ok(()),
+        }
+    }
+
+    /* blocks until the update with the marker `update` returned is on the panel. the kernel
+     * variant is only known by trying, a kernel that doesn't know the ioctl says ENOTTY.
+     */
+    pub fn wait(&mut self, marker: u32) -> io::Result<()> {
+        match self.wait_ioctl(self.wait_ioctl, marker) {
+            Err(e) if e.raw_os_error() == Some(libc::ENOTTY) => {
+                let other = match self.wait_ioctl {
+                    WaitIoctl::Marker => WaitIoctl::MarkerData,
+                    WaitIoctl::MarkerData => WaitIoctl::Marker,
+                };
+                self.wait_ioctl(other, marker)?;
+                self.wait_ioctl = other;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+impl Display for Framebuffer {
+    // the visible resolution, not the physical size in mm that var_info.width/height hold
+    fn width(&self) -> u32 {
+        let info = &self.var_info;
+        info.xres
+    }
+
+    fn height(&self) -> u32 {
+        let info = &self.var_info;
+        info.yres
+    }
+
+    fn set_pixel(&mut self, p: Point, rgb: [u8; 3]) {
+        Framebuffer::set_pixel(self, p, rgb)
+    }
+
+    fn blit_gray(&mut self, rect: &MxcfbRect, pixels: &[u8], stride: usize) {
+        Framebuffer::blit_gray(self, rect, pixels, stride)
+    }
+
+    fn fill_rect(&mut self, rect: &MxcfbRect, gray: u8) {
+        Framebuffer::fill_rect(self, rect, gray)
+    }
+
+    fn update(&mut self, rect: MxcfbRect, mode: Mode) -> io::Result<u32> {
+        Framebuffer::update(self, rect, mode)
+    }
+
+    fn wait(&mut self, marker: u32) -> io::Result<()> {
+        Framebuffer::wait(self, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(offset: u32, length: u32) -> Bitfield {
+        Bitfield {
+            offset,
+            length,
+            msb_right: 0,
         }
     }
+
+    // how the bitfields of the common layouts look in fb_var_screeninfo
+    fn info(bits_per_pixel: u32, rgbt: [(u32, u32); 4]) -> VarScreenInfo {
+        VarScreenInfo {
+            bits_per_pixel,
+            red: field(rgbt[0].0, rgbt[0].1),
+            green: field(rgbt[1].0, rgbt[1].1),
+            blue: field(rgbt[2].0, rgbt[2].1),
+            transp: field(rgbt[3].0, rgbt[3].1),
+            ..Default::default()
+        }
+    }
+
+    const RGB565: [(u32, u32); 4] = [(11, 5), (5, 6), (0, 5), (0, 0)];
+    const XRGB8888: [(u32, u32); 4] = [(16, 8), (8, 8), (0, 8), (24, 0)];
+    const ARGB8888: [(u32, u32); 4] = [(16, 8), (8, 8), (0, 8), (24, 8)];
+
+    #[test]
+    fn packs_pixels() {
+        let gray8 = PixelFormat::detect(&info(8, [(0, 8); 4])).unwrap();
+        let rgb565 = PixelFormat::detect(&info(16, RGB565)).unwrap();
+        let xrgb = PixelFormat::detect(&info(32, XRGB8888)).unwrap();
+        let argb = PixelFormat::detect(&info(32, ARGB8888)).unwrap();
+        for (format, black, white, gray) in [
+            (&gray8, 0, 0xff, 0x80),
+            (&rgb565, 0, 0xffff, 0x8410),
+            (&xrgb, 0, 0xffffff, 0x808080),
+            // the alpha channel is always opaque
+            (&argb, 0xff000000, 0xffffffff, 0xff808080),
+        ] {
+            let pack = |v: u8| u32::from_ne_bytes(format.pack([v, v, v]));
+            assert_eq!(pack(0), black, "{:?}", format);
+            assert_eq!(pack(0xff), white, "{:?}", format);
+            assert_eq!(pack(0x80), gray, "{:?}", format);
+        }
+
+        // only the luminance of colors is left
+        assert_eq!(gray8.pack([255, 0, 0])[0], luma([255, 0, 0]));
+        assert_eq!(u32::from_ne_bytes(rgb565.pack([255, 0, 0])), 0xf800);
+    }
+
+    #[test]
+    fn detects_pixel_formats() {
+        assert!(matches!(
+            PixelFormat::detect(&info(8, [(0, 8); 4])),
+            Ok(PixelFormat::Gray8)
+        ));
+        let format = PixelFormat::detect(&info(16, RGB565)).unwrap();
+        assert_eq!(format.bytes(), 2);
+        assert!(matches!(
+            format,
+            PixelFormat::Rgb { red, green, .. } if red.offset == 11 && green.length == 6
+        ));
+        let format = PixelFormat::detect(&info(32, XRGB8888)).unwrap();
+        assert_eq!(format.bytes(), 4);
+        assert!(matches!(format, PixelFormat::Rgb { transp, .. } if transp.length == 0));
+
+        let error = PixelFormat::detect(&info(24, XRGB8888)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(error.to_string(), "24 bits per pixel are not supported");
+    }
 }
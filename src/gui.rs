@@ -1,7 +1,11 @@
-use crate::Region;
+use crate::error::Error;
+use crate::fonts::{FontChain, FontRegistry};
+use crate::layout::Size;
+use crate::screen::Canvas;
 use image::DynamicImage;
-use rusttype::{Font, PositionedGlyph, Scale};
-use std::path::Path;
+use rusttype::{PositionedGlyph, Scale};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 
 use crate::util::{Color, FontSetting, Point};
 use image::imageops::FilterType;
@@ -9,166 +13,609 @@ use image::io::Reader as ImageReader;
 
 // Text, Images, the list goes on...
 pub trait Drawable {
-    fn draw(&self, region: &Region) -> Result<(), &str>;
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error>;
+    // room it takes out of `available`, see layout for how containers use this
+    fn measure(&self, available: Size) -> Size;
+    // moves it to `pos` in its parent, with `size` decided by the container
+    fn arrange(&mut self, pos: Point, size: Size);
 }
 
 pub struct Image {
+    // for errors, the picture is decoded once in `new`
+    path: PathBuf,
     data: DynamicImage,
-    pos: Point,
-    width: u32,
-    height: u32,
+    pub pos: Point,
+    pub width: u32,
+    pub height: u32,
 }
 
 impl Image {
-    pub fn new(filename: &str, pos: Point, scale: f32) -> Box<Image> {
-        let file = Path::new(filename);
-        let data = ImageReader::open(file).unwrap().decode().unwrap();
+    pub fn new<P: AsRef<Path>>(filename: P, pos: Point, scale: f32) -> Result<Box<Image>, Error> {
+        let file = filename.as_ref();
+        let data = ImageReader::open(file)
+            .map_err(|e| Error::Image(file.to_owned(), e.into()))?
+            .decode()
+            .map_err(|e| Error::Image(file.to_owned(), e))?;
         let w: f32 = data.width() as f32;
         let h: f32 = data.height() as f32;
 
         let data = data.resize((w * scale) as u32, (h * scale) as u32, FilterType::Gaussian);
         let width = data.width();
         let height = data.height();
-        Box::new(Image {
+        Ok(Box::new(Image {
+            path: file.to_owned(),
             data,
             pos,
             width,
             height,
-        })
+        }))
     }
 }
 
 impl Drawable for Image {
-    fn draw(&self, region: &Region) -> Result<(), &str> {
-        let screen = region.screen.unwrap();
-        if region.pos.x + self.pos.x + self.data.width() > screen.width
-            || region.pos.y + self.pos.y + self.data.height() > screen.height
-        {
-            return Err("image outside of frame");
-        }
-        // turn into grayscale and convert transparent background to white
-        let mut img = self.data.to_luma_alpha8();
-        for px in img.pixels_mut() {
-            px.0[0] = 255 - (px.0[0] * px.0[1]);
-        }
-
-        for x in 0..img.width() {
-            for y in 0..img.height() {
-                let px = img.get_pixel(x, y);
-                if let Err(_) = region.plot(
-                    self.pos + Point::new(x, y),
-                    Color::new(px.0[0], px.0[0], px.0[0]),
-                ) {
-                    return Err("image outside of frame");
-                }
-            }
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        if self.pos.x >= canvas.width() || self.pos.y >= canvas.height() {
+            return Err(Error::ImagePlacement(
+                self.path.clone(),
+                format!(
+                    "{}x{} at {},{} is outside of the {}x{} frame",
+                    self.data.width(),
+                    self.data.height(),
+                    self.pos.x,
+                    self.pos.y,
+                    canvas.width(),
+                    canvas.height()
+                ),
+            ));
         }
-        Ok(())
+        // what sticks out is cut off, like text that doesn't fit
+        let width = self.data.width().min(canvas.width() - self.pos.x);
+        let height = self.data.height().min(canvas.height() - self.pos.y);
+        let img = self.data.crop_imm(0, 0, width, height).to_luma_alpha8();
+
+        // icons are mostly transparent and painted over what is below them, opaque pictures can
+        // take the fast path
+        if img.pixels().all(|px| px.0[1] == 255) {
+            let gray: Vec<u8> = img.pixels().map(|px| px.0[0]).collect();
+            return canvas.blit_gray(self.pos, img.width(), img.height(), &gray);
+        }
+
+        canvas.composite(self.pos, img.width(), img.height(), img.as_raw())
+    }
+
+    fn measure(&self, _available: Size) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    // pictures keep their size, a bigger slot only moves them
+    fn arrange(&mut self, pos: Point, _size: Size) {
+        self.pos = pos;
     }
 }
 
-pub struct Text<'a> {
+pub struct Text {
     // text: String
     pub pos: Point,
-    pub data: Vec<PositionedGlyph<'a>>,
+    pub data: Vec<PositionedGlyph<'static>>,
     pub width: u32,
     pub height: u32,
     pub font_info: FontSetting,
 }
 
-impl<'a> Text<'a> {
-    pub fn new(text: &str, pos: Point, font_info: FontSetting) -> Box<Text<'a>> {
-        let path = std::env::current_dir()
-            .unwrap()
-            .join("fonts/")
-            .join(&font_info.name);
+impl Text {
+    pub fn new(
+        fonts: &FontRegistry,
+        text: &str,
+        pos: Point,
+        font_info: FontSetting,
+    ) -> Result<Box<Text>, Error> {
+        let font = fonts.chain(&font_info.name)?;
         let size = Scale::uniform(font_info.size);
-
-        let data = std::fs::read(&path).unwrap();
-
-        let font = Font::try_from_vec(data).unwrap();
         let v_metrics = font.v_metrics(size);
 
-        let glyphs: Vec<_> = font
-            .layout(text, size, rusttype::point(0.0, v_metrics.ascent))
-            .collect();
-
+        let glyphs = font.layout(text, size, rusttype::point(0.0, v_metrics.ascent));
         let glyphs_height = (v_metrics.ascent - v_metrics.descent).ceil() as u32;
-        let glyphs_width = {
-            let min_x = glyphs
-                .first()
-                .map(|g| g.pixel_bounding_box().unwrap().min.x)
-                .unwrap();
-            let max_x = glyphs
-                .last()
-                .map(|g| g.pixel_bounding_box().unwrap().max.x)
-                .unwrap();
-            (max_x - min_x) as u32
-        };
+        let glyphs_width = font.measure(text, size).ceil() as u32;
 
-        Box::new(Text {
+        Ok(Box::new(Text {
             pos,
             data: glyphs,
             width: glyphs_width,
             height: glyphs_height,
             font_info,
-        })
+        }))
+    }
+
+    /* lays out `text` in a box of `bounds` at `pos`, wrapped, aligned and cut or shrunk to fit as
+     * `bounds` says. width and height of the result are those of the box.
+     */
+    pub fn layout(
+        fonts: &FontRegistry,
+        text: &str,
+        pos: Point,
+        mut font_info: FontSetting,
+        bounds: &TextBox,
+    ) -> Result<Box<Text>, Error> {
+        let font = fonts.chain(&font_info.name)?;
+
+        let (mut lines, mut fits) = break_lines(&font, font_info.size, text, bounds);
+        while !fits && bounds.overflow == Overflow::Shrink && font_info.size > MIN_SHRINK_SIZE {
+            font_info.size = (font_info.size * 0.9).max(MIN_SHRINK_SIZE);
+            (lines, fits) = break_lines(&font, font_info.size, text, bounds);
+        }
+
+        let scale = Scale::uniform(font_info.size);
+        let metrics = LineMetrics::new(&font, scale, bounds.line_spacing);
+        let max_lines = bounds.height.map_or(usize::MAX, |h| metrics.max_lines(h));
+        if !fits {
+            let cut = lines.len() > max_lines;
+            lines.truncate(max_lines);
+            // shrinking as far as we go wasn't enough, better to lose the end than to not show
+            // anything
+            if bounds.overflow != Overflow::Clip {
+                let last = lines.len() - 1;
+                for (i, line) in lines.iter_mut().enumerate() {
+                    *line = ellipsize(&font, scale, line, bounds.width, cut && i == last);
+                }
+            }
+        }
+
+        let content_height = metrics.height(lines.len());
+        let height = bounds.height.unwrap_or(content_height.ceil() as u32);
+        let top = bounds.valign.offset(height as f32, content_height);
+        let mut glyphs = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let left = bounds
+                .align
+                .offset(bounds.width as f32, font.measure(line, scale));
+            let baseline = top + metrics.ascent + i as f32 * metrics.advance;
+            glyphs.extend(font.layout(line, scale, rusttype::point(left, baseline)));
+        }
+        if bounds.overflow == Overflow::Clip {
+            glyphs.retain(|g| {
+                g.pixel_bounding_box()
+                    .is_none_or(|bb| bb.max.x <= bounds.width as i32 && bb.max.y <= height as i32)
+            });
+        }
+
+        Ok(Box::new(Text {
+            pos,
+            data: glyphs,
+            width: bounds.width,
+            height,
+            font_info,
+        }))
     }
 }
 
-impl<'a> Drawable for Text<'a> {
-    fn draw(&self, region: &Region) -> Result<(), &str> {
-        let screen = region.screen.unwrap();
-        if region.pos.x + self.pos.x > screen.width || region.pos.y + self.pos.y > screen.height {
-            return Err("text position out of screen bounds");
+/* where in its box a text is put, horizontally or vertically */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+impl Align {
+    // distance of content of size `content` from the start of a box of size `size`
+    fn offset(&self, size: f32, content: f32) -> f32 {
+        match self {
+            Align::Start => 0.0,
+            Align::Center => ((size - content) / 2.0).max(0.0),
+            Align::End => (size - content).max(0.0),
         }
+    }
+}
+
+/* what happens to text that doesn't fit its box */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overflow {
+    // whatever sticks out is not drawn
+    Clip,
+    // the last line that fits ends in "…"
+    #[default]
+    Ellipsis,
+    // the font gets smaller until the text fits, down to MIN_SHRINK_SIZE
+    Shrink,
+}
 
-        if region.pos.x + self.pos.x + self.width > screen.width
-            || region.pos.y + self.pos.y + self.height > screen.height
-        {
-            return Err("text exceeds screen width");
+// smallest size Overflow::Shrink goes down to, anything smaller is unreadable on the panel
+const MIN_SHRINK_SIZE: f32 = 12.0;
+
+/* box a text is laid out in by `Text::layout` */
+#[derive(Debug, Clone)]
+pub struct TextBox {
+    pub width: u32,
+    // None grows with the lines of text
+    pub height: Option<u32>,
+    // break lines at spaces, otherwise only at '\n'
+    pub wrap: bool,
+    pub align: Align,
+    pub valign: Align,
+    // distance of two baselines, 1.0 is what the font suggests
+    pub line_spacing: f32,
+    pub overflow: Overflow,
+}
+
+impl TextBox {
+    // a single line that ends in "…" if it is too long
+    pub fn line(width: u32) -> TextBox {
+        TextBox {
+            width,
+            height: None,
+            wrap: false,
+            align: Align::Start,
+            valign: Align::Start,
+            line_spacing: 1.0,
+            overflow: Overflow::Ellipsis,
+        }
+    }
+
+    // as many lines as it takes
+    pub fn paragraph(width: u32) -> TextBox {
+        TextBox {
+            wrap: true,
+            ..TextBox::line(width)
+        }
+    }
+}
+
+struct LineMetrics {
+    ascent: f32,
+    // ascent to descent
+    line_height: f32,
+    // baseline to baseline
+    advance: f32,
+}
+
+impl LineMetrics {
+    fn new(font: &FontChain, scale: Scale, spacing: f32) -> LineMetrics {
+        let v_metrics = font.v_metrics(scale);
+        let line_height = v_metrics.ascent - v_metrics.descent;
+        LineMetrics {
+            ascent: v_metrics.ascent,
+            line_height,
+            advance: (line_height + v_metrics.line_gap) * spacing,
+        }
+    }
+
+    fn height(&self, lines: usize) -> f32 {
+        self.line_height + lines.saturating_sub(1) as f32 * self.advance
+    }
+
+    // at least one, a box too low for a single line still shows its top
+    fn max_lines(&self, height: u32) -> usize {
+        let extra = (height as f32 - self.line_height) / self.advance;
+        1 + extra.max(0.0).floor() as usize
+    }
+}
+
+/* `text` split into lines for `bounds` at font size `size`, and whether they fit */
+fn break_lines(font: &FontChain, size: f32, text: &str, bounds: &TextBox) -> (Vec<String>, bool) {
+    let scale = Scale::uniform(size);
+    let width = bounds.width as f32;
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        if bounds.wrap {
+            lines.extend(wrap(font, scale, paragraph, width));
+        } else {
+            lines.push(paragraph.to_string());
+        }
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+
+    let metrics = LineMetrics::new(font, scale, bounds.line_spacing);
+    let fits = bounds
+        .height
+        .is_none_or(|h| lines.len() <= metrics.max_lines(h))
+        && lines.iter().all(|line| font.measure(line, scale) <= width);
+    (lines, fits)
+}
+
+// greedy word wrap, words longer than a line are split
+fn wrap(font: &FontChain, scale: Scale, text: &str, width: f32) -> Vec<String> {
+    let fits = |s: &str| font.measure(s, scale) <= width;
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let joined = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if fits(&joined) {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        let mut rest = word;
+        while !fits(rest) {
+            // at least one character per line, even if that doesn't fit either
+            let split = rest
+                .char_indices()
+                .skip(1)
+                .map(|(i, _)| i)
+                .take_while(|i| fits(&rest[..*i]))
+                .last()
+                .unwrap_or_else(|| rest.chars().next().map_or(rest.len(), char::len_utf8));
+            lines.push(rest[..split].to_string());
+            rest = &rest[split..];
+        }
+        line = rest.to_string();
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/* `line` shortened until it fits `width` with "…" appended. `force` appends it even if the line
+ * fits, to show that lines after it were cut
+ */
+fn ellipsize(font: &FontChain, scale: Scale, line: &str, width: u32, force: bool) -> String {
+    let width = width as f32;
+    if !force && font.measure(line, scale) <= width {
+        return line.to_string();
+    }
+    // not every font has the ellipsis character
+    let ellipsis = if font.has_glyph('…') { "…" } else { "..." };
+
+    let mut line = line.trim_end().to_string();
+    while !line.is_empty() && font.measure(&format!("{}{}", line, ellipsis), scale) > width {
+        line.pop();
+        line.truncate(line.trim_end().len());
+    }
+    line + ellipsis
+}
+
+impl Drawable for Text {
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        if self.pos.x >= canvas.width() || self.pos.y >= canvas.height() {
+            return Err(Error::Render("text position out of screen bounds"));
         }
 
         for glyph in &self.data {
             if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                // v is how much of the pixel the glyph covers, the text is painted over whatever
+                // is below with that, so antialiased edges blend into images and shading
                 glyph.draw(|x, y, v| {
-                    let c = (255.0 - 255.0 * v * self.font_info.saturation) as u8;
-                    let _ = region.plot(
-                        // we already check if pixel is out of bounds
-                        Point::new(
-                            self.pos.x + x + bounding_box.min.x as u32,
-                            self.pos.y + y + bounding_box.min.y as u32,
-                        ),
-                        Color::new(c, c, c),
-                    );
+                    // glyphs like "j" can reach left of the pen position, and so of the text
+                    let x = self.pos.x as i32 + x as i32 + bounding_box.min.x;
+                    let y = self.pos.y as i32 + y as i32 + bounding_box.min.y;
+                    if x < 0 || y < 0 {
+                        return;
+                    }
+                    let alpha = (255.0 * v * self.font_info.saturation).round() as u8;
+                    // pixels past the edge of the canvas are cut off
+                    let _ = canvas.blend(Point::new(x as u32, y as u32), Color::gray(0), alpha);
                 });
             }
         }
         Ok(())
     }
-}
 
-pub trait BoundingBox {
-    fn right_of(&self, margin: u32) -> Point;
-    fn below_of(&self, margin: u32) -> Point;
+    fn measure(&self, _available: Size) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    // the glyphs are laid out when the text is made, a bigger slot only moves them
+    fn arrange(&mut self, pos: Point, _size: Size) {
+        self.pos = pos;
+    }
 }
 
-impl<'a> BoundingBox for Text<'a> {
-    fn right_of(&self, margin: u32) -> Point {
-        self.pos + Point::new(self.width + margin, 0)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::MemoryDisplay;
+    use crate::dither::Dither;
+    use crate::fonts::DEFAULT_FONT;
+    use crate::screen::{Rotation, Screen};
+
+    fn font(size: f32) -> FontSetting {
+        FontSetting {
+            name: DEFAULT_FONT.to_string(),
+            size,
+            saturation: 1.0,
+        }
     }
 
-    fn below_of(&self, margin: u32) -> Point {
-        self.pos + Point::new(0, self.height + margin)
+    fn layout(fonts: &FontRegistry, text: &str, size: f32, bounds: &TextBox) -> Box<Text> {
+        Text::layout(fonts, text, Point::new(0, 0), font(size), bounds).unwrap()
     }
-}
 
-impl BoundingBox for Image {
-    fn right_of(&self, margin: u32) -> Point {
-        self.pos + Point::new(self.width + margin, 0)
+    // the baselines of the lines of a text, top to bottom
+    fn baselines(text: &Text) -> Vec<f32> {
+        let mut baselines: Vec<f32> = text.data.iter().map(|g| g.position().y).collect();
+        baselines.dedup();
+        baselines
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        let fonts = FontRegistry::default();
+        let chain = fonts.chain(DEFAULT_FONT).unwrap();
+        let scale = Scale::uniform(20.0);
+        let width = chain.measure("rain and", scale) + 1.0;
+        assert_eq!(
+            wrap(&chain, scale, "rain and  snow later", width),
+            ["rain and", "snow", "later"]
+        );
+        // only at '\n' without wrapping
+        let bounds = TextBox::line(width as u32);
+        let (lines, fits) = break_lines(&chain, 20.0, "rain and snow\nlater", &bounds);
+        assert_eq!(lines, ["rain and snow", "later"]);
+        assert!(!fits);
+    }
+
+    #[test]
+    fn splits_words_longer_than_a_line() {
+        let fonts = FontRegistry::default();
+        let chain = fonts.chain(DEFAULT_FONT).unwrap();
+        let scale = Scale::uniform(20.0);
+        let width = chain.measure("abcd", scale) + 1.0;
+        let lines = wrap(&chain, scale, "abcdefghijk xy", width);
+        assert!(lines.len() > 2);
+        assert!(lines.iter().all(|l| chain.measure(l, scale) <= width));
+        assert_eq!(lines[..lines.len() - 1].concat(), "abcdefghijk");
+        assert_eq!(lines.last().unwrap(), "xy");
+
+        // a single character per line if not even that fits
+        assert_eq!(wrap(&chain, scale, "ab", 1.0), ["a", "b"]);
+    }
+
+    #[test]
+    fn ends_cut_text_in_an_ellipsis() {
+        let fonts = FontRegistry::default();
+        let chain = fonts.chain(DEFAULT_FONT).unwrap();
+        let scale = Scale::uniform(20.0);
+        let line = chain.v_metrics(scale).ascent - chain.v_metrics(scale).descent;
+        let bounds = TextBox {
+            height: Some((line * 1.5) as u32),
+            ..TextBox::paragraph(120)
+        };
+        let text = layout(
+            &fonts,
+            "light rain and snow with a thunderstorm",
+            20.0,
+            &bounds,
+        );
+        assert_eq!(baselines(&text).len(), 1);
+        assert_eq!(text.height, bounds.height.unwrap());
+        let ellipsis = chain.layout("…", scale, rusttype::point(0.0, 0.0))[0].id();
+        assert_eq!(text.data.last().unwrap().id(), ellipsis);
+
+        // the line before the cut gets one even though it fits
+        let cut = ellipsize(&chain, scale, "light rain", 120, true);
+        assert_eq!(cut, "light rain…");
+        assert_eq!(
+            ellipsize(&chain, scale, "light rain", 120, false),
+            "light rain"
+        );
+        let cut = ellipsize(&chain, scale, "thunderstorm and rain", 120, false);
+        assert!(cut.ends_with('…') && chain.measure(&cut, scale) <= 120.0);
+    }
+
+    #[test]
+    fn shrinks_down_to_the_smallest_size() {
+        let fonts = FontRegistry::default();
+        let bounds = TextBox {
+            overflow: Overflow::Shrink,
+            ..TextBox::line(200)
+        };
+        let text = layout(&fonts, "light rain", 40.0, &bounds);
+        assert!(text.font_info.size < 40.0 && text.font_info.size > MIN_SHRINK_SIZE);
+
+        let long = "light rain and snow with a thunderstorm in the afternoon";
+        let text = layout(&fonts, long, 40.0, &bounds);
+        assert_eq!(text.font_info.size, MIN_SHRINK_SIZE);
+        // what still doesn't fit is cut off with an ellipsis
+        let chain = fonts.chain(DEFAULT_FONT).unwrap();
+        let ellipsis = chain.layout(
+            "…",
+            Scale::uniform(MIN_SHRINK_SIZE),
+            rusttype::point(0.0, 0.0),
+        );
+        assert_eq!(text.data.last().unwrap().id(), ellipsis[0].id());
+    }
+
+    #[test]
+    fn aligns_in_the_box() {
+        let fonts = FontRegistry::default();
+        let chain = fonts.chain(DEFAULT_FONT).unwrap();
+        let scale = Scale::uniform(20.0);
+        let width = chain.measure("rain", scale);
+        let metrics = LineMetrics::new(&chain, scale, 1.0);
+        for (align, left) in [
+            (Align::Start, 0.0),
+            (Align::Center, (200.0 - width) / 2.0),
+            (Align::End, 200.0 - width),
+        ] {
+            for (valign, top) in [
+                (Align::Start, 0.0),
+                (Align::Center, (100.0 - metrics.line_height) / 2.0),
+                (Align::End, 100.0 - metrics.line_height),
+            ] {
+                let bounds = TextBox {
+                    height: Some(100),
+                    align,
+                    valign,
+                    ..TextBox::line(200)
+                };
+                let text = layout(&fonts, "rain", 20.0, &bounds);
+                let first = text.data[0].position();
+                assert!((first.x - left).abs() < 0.01, "{:?}", align);
+                assert!(
+                    (first.y - top - metrics.ascent).abs() < 0.01,
+                    "{:?}",
+                    valign
+                );
+                assert_eq!((text.width, text.height), (200, 100));
+            }
+        }
+
+        // content bigger than the box stays at the start
+        assert_eq!(Align::End.offset(10.0, 20.0), 0.0);
+        assert_eq!(Align::Center.offset(10.0, 20.0), 0.0);
     }
 
-    fn below_of(&self, margin: u32) -> Point {
-        self.pos + Point::new(0, self.height + margin)
+    #[test]
+    fn blends_text_over_what_is_below() {
+        let fonts = FontRegistry::default();
+        let screen = Screen::with_display(MemoryDisplay::new(40, 40), Rotation::R0);
+        screen.set_dither(Dither::None);
+        screen
+            .fill_rect(Point::new(0, 0), 40, 40, Color::gray(0x88))
+            .unwrap();
+        let text = Text::new(&fonts, "O", Point::new(4, 0), font(32.0)).unwrap();
+        text.draw(&screen).unwrap();
+        screen.update().unwrap();
+
+        let fb = screen.fb.borrow();
+        let grays: Vec<u8> = (0..40 * 40)
+            .map(|i| fb.pixel(Point::new(i % 40, i / 40))[0])
+            .collect();
+        // ink, fill, and edges in between that are neither
+        assert!(grays.contains(&0));
+        assert!(grays.contains(&0x88));
+        assert!(grays.iter().any(|v| *v > 0 && *v < 0x88));
+        // the fill only ever gets darker
+        assert!(grays.iter().all(|v| *v <= 0x88));
+    }
+
+    #[test]
+    fn names_images_outside_of_the_frame() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("icons/01d.png");
+        let image = Image::new(&path, Point::new(0, 0), 0.5).unwrap();
+        let (width, height) = (image.width, image.height);
+        let screen = Screen::with_display(MemoryDisplay::new(width + 9, height), Rotation::R0);
+        assert!(image.draw(&screen).is_ok());
+
+        // partly outside is cut off
+        let image = Image::new(&path, Point::new(10, 0), 0.5).unwrap();
+        assert!(image.draw(&screen).is_ok());
+
+        let image = Image::new(&path, Point::new(width + 9, 0), 0.5).unwrap();
+        let error = image.draw(&screen).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            format!(
+                "image {}: {}x{} at {},0 is outside of the {}x{} frame",
+                path.display(),
+                width,
+                height,
+                width + 9,
+                width + 9,
+                height
+            )
+        );
     }
 }
@@ -0,0 +1,739 @@
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/* Minimal HTTP/1.1 client, just enough to talk to the OpenWeather API (or a stub server during
+ * development): GET only, one request per connection, redirects are followed.
+ * https goes through rustls. plain http has to be allowed explicitly, otherwise our API key is sent
+ * in the clear.
+ */
+
+const MAX_REDIRECTS: u8 = 5;
+// we never expect anything near this, but a broken server shouldn't make us eat all memory
+const MAX_BODY: usize = 4 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum HttpError {
+    InvalidUrl(String),
+    Io(io::Error),
+    Timeout,
+    Malformed(&'static str),
+    TooManyRedirects,
+    InsecureUrl(String),
+    Tls(rustls::Error),
+    CaFile(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::InvalidUrl(url) => write!(f, "invalid url {}", url),
+            HttpError::Io(e) => write!(f, "connection failed: {}", e),
+            HttpError::Timeout => write!(f, "connection timed out"),
+            HttpError::Malformed(what) => write!(f, "malformed response: {}", what),
+            HttpError::TooManyRedirects => write!(f, "too many redirects"),
+            HttpError::InsecureUrl(url) => write!(f, "refusing plain http for {}", url),
+            HttpError::Tls(e) => write!(f, "TLS error: {}", e),
+            HttpError::CaFile(msg) => write!(f, "cannot load CA bundle: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<io::Error> for HttpError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => HttpError::Timeout,
+            // rustls reports handshake and certificate failures as io errors wrapping its own
+            _ => match e
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+            {
+                Some(tls) => HttpError::Tls(tls.clone()),
+                None => HttpError::Io(e),
+            },
+        }
+    }
+}
+
+/* trust anchors for https. the Kobo's own certificate store is old or missing depending on the
+ * firmware, so by default we use the Mozilla roots compiled into the binary. a PEM bundle can be
+ * given instead, e.g. for a stub server with a self-signed certificate.
+ */
+pub fn tls_config(ca_file: Option<&Path>) -> Result<Arc<ClientConfig>, HttpError> {
+    let mut roots = RootCertStore::empty();
+    match ca_file {
+        Some(path) => {
+            let certs = CertificateDer::pem_file_iter(path)
+                .map_err(|e| HttpError::CaFile(format!("{}: {}", path.display(), e)))?;
+            for cert in certs {
+                let cert =
+                    cert.map_err(|e| HttpError::CaFile(format!("{}: {}", path.display(), e)))?;
+                roots.add(cert).map_err(HttpError::Tls)?;
+            }
+            if roots.is_empty() {
+                return Err(HttpError::CaFile(format!(
+                    "{}: no certificates found",
+                    path.display()
+                )));
+            }
+        }
+        None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(HttpError::Tls)?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Url {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    // path including the query string, always starts with '/'
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Url, HttpError> {
+        let invalid = || HttpError::InvalidUrl(url.to_string());
+
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(invalid());
+        };
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) if rest[i..].starts_with('/') => (&rest[..i], rest[i..].to_string()),
+            Some(i) => (&rest[..i], format!("/{}", &rest[i..])),
+            None => (rest, "/".to_string()),
+        };
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = match authority.strip_prefix('[') {
+            // IPv6 addresses are in brackets, the colons in them don't start the port
+            Some(rest) => match rest.split_once(']').ok_or_else(invalid)? {
+                (host, "") => (host, default_port),
+                (host, port) => match port.strip_prefix(':') {
+                    Some(port) => (host, port.parse().map_err(|_| invalid())?),
+                    None => return Err(invalid()),
+                },
+            },
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                None => (authority, default_port),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Url {
+            tls,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /* resolves the Location header of a redirect against this url, like a browser resolves a
+     * link (RFC 3986): relative paths are taken from the directory of the current path
+     */
+    fn join(&self, location: &str) -> Result<Url, HttpError> {
+        // the fragment is for the client and never sent
+        let location = location.split('#').next().unwrap_or("");
+        if location.starts_with("http://") || location.starts_with("https://") {
+            return Url::parse(location);
+        }
+        if let Some(rest) = location.strip_prefix("//") {
+            let scheme = if self.tls { "https" } else { "http" };
+            return Url::parse(&format!("{}://{}", scheme, rest));
+        }
+
+        // a scheme we can't follow, like ftp:
+        let scheme = location.split_once(':').map_or("", |(scheme, _)| scheme);
+        let scheme_chars = |c: char| c.is_ascii_alphanumeric() || "+-.".contains(c);
+        if scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(scheme_chars)
+        {
+            return Err(HttpError::InvalidUrl(location.to_string()));
+        }
+
+        let current = self.path.split('?').next().unwrap_or("/");
+        let path = match location.chars().next() {
+            None => self.path.clone(),
+            Some('?') => format!("{}{}", current, location),
+            Some('/') => location.to_string(),
+            Some(_) => {
+                let dir = &current[..=current.rfind('/').unwrap_or(0)];
+                format!("{}{}", dir, location)
+            }
+        };
+        Ok(Url {
+            path: remove_dot_segments(&path),
+            ..self.clone()
+        })
+    }
+
+    fn host_header(&self) -> String {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+        match (self.tls, self.port) {
+            (false, 80) | (true, 443) => host,
+            (_, port) => format!("{}:{}", host, port),
+        }
+    }
+}
+
+/* `path` without "." and ".." segments, which may be in relative redirects. the query is left
+ * alone
+ */
+fn remove_dot_segments(path: &str) -> String {
+    let (path, query) = match path.find('?') {
+        Some(i) => path.split_at(i),
+        None => (path, ""),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, segment) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        match *segment {
+            "." | ".." => {
+                if *segment == ".." {
+                    segments.pop();
+                }
+                // "a/.." is the directory "a/" was in, not a file
+                if last {
+                    segments.push("");
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}{}", segments.join("/"), query)
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+pub struct Client {
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    tls: Arc<ClientConfig>,
+    allow_http: bool,
+}
+
+impl Client {
+    pub fn new(
+        connect_timeout: Duration,
+        read_timeout: Duration,
+        tls: Arc<ClientConfig>,
+        allow_http: bool,
+    ) -> Client {
+        Client {
+            connect_timeout,
+            read_timeout,
+            tls,
+            allow_http,
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Result<Response, HttpError> {
+        let mut url = Url::parse(url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            // also catches redirects from https to http
+            if !url.tls && !self.allow_http {
+                return Err(HttpError::InsecureUrl(url.host));
+            }
+            let response = self.request(&url)?;
+            match response.status {
+                301 | 302 | 303 | 307 | 308 => {
+                    let location = response
+                        .header("Location")
+                        .ok_or(HttpError::Malformed("redirect without location"))?;
+                    url = url.join(location)?;
+                }
+                _ => return Ok(response),
+            }
+        }
+        Err(HttpError::TooManyRedirects)
+    }
+
+    fn connect_tcp(&self, url: &Url) -> Result<TcpStream, HttpError> {
+        let mut last_error = None;
+        for addr in (url.host.as_str(), url.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.connect_timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(match last_error {
+            Some(e) => e.into(),
+            None => HttpError::InvalidUrl(url.host.clone()),
+        })
+    }
+
+    fn connect(&self, url: &Url) -> Result<Stream, HttpError> {
+        let tcp = self.connect_tcp(url)?;
+        tcp.set_read_timeout(Some(self.read_timeout))?;
+        tcp.set_write_timeout(Some(self.read_timeout))?;
+
+        if !url.tls {
+            return Ok(Stream::Plain(tcp));
+        }
+
+        let name = ServerName::try_from(url.host.clone())
+            .map_err(|_| HttpError::InvalidUrl(url.host.clone()))?;
+        let connection = ClientConnection::new(self.tls.clone(), name).map_err(HttpError::Tls)?;
+        Ok(Stream::Tls(Box::new(StreamOwned::new(connection, tcp))))
+    }
+
+    fn request(&self, url: &Url) -> Result<Response, HttpError> {
+        let mut stream = self.connect(url)?;
+
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+            url.path,
+            url.host_header()
+        );
+        stream.write_all(request.as_bytes())?;
+
+        read_response(BufReader::new(stream))
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, HttpError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(HttpError::Malformed("connection closed early"));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_response<R: BufRead>(mut reader: R) -> Result<Response, HttpError> {
+    let status_line = read_line(&mut reader)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or(HttpError::Malformed("invalid status line"))?;
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or(HttpError::Malformed("invalid header"))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let mut response = Response {
+        status,
+        headers,
+        body: Vec::new(),
+    };
+
+    let chunked = response
+        .header("Transfer-Encoding")
+        .is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+    let content_length = response.header("Content-Length").map(str::parse::<usize>);
+
+    response.body = if chunked {
+        read_chunked(&mut reader)?
+    } else if let Some(length) = content_length {
+        let length = length.map_err(|_| HttpError::Malformed("invalid content length"))?;
+        if length > MAX_BODY {
+            return Err(HttpError::Malformed("body too large"));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => HttpError::Malformed("body shorter than announced"),
+            _ => e.into(),
+        })?;
+        body
+    } else {
+        let mut body = Vec::new();
+        reader.take(MAX_BODY as u64 + 1).read_to_end(&mut body)?;
+        if body.len() > MAX_BODY {
+            return Err(HttpError::Malformed("body too large"));
+        }
+        body
+    };
+
+    Ok(response)
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, HttpError> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        // chunk extensions after ';' are allowed but meaningless to us
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| HttpError::Malformed("invalid chunk size"))?;
+        if size == 0 {
+            break;
+        }
+        // a size close to usize::MAX mustn't wrap around
+        if size > MAX_BODY - body.len() {
+            return Err(HttpError::Malformed("body too large"));
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        if !read_line(reader)?.is_empty() {
+            return Err(HttpError::Malformed("missing chunk terminator"));
+        }
+    }
+
+    // skip trailers. some servers close the connection right after the last chunk, that's fine
+    while let Ok(line) = read_line(reader) {
+        if line.is_empty() {
+            break;
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    fn client(allow_http: bool) -> Client {
+        let timeout = Duration::from_secs(5);
+        Client::new(timeout, timeout, tls_config(None).unwrap(), allow_http)
+    }
+
+    /* answers one connection after the other with `responses` on a free port of localhost.
+     * returns the port and the request lines that came in
+     */
+    fn serve(responses: Vec<String>) -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut lines = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim_end().is_empty() {
+                        break;
+                    }
+                    lines.push(line.trim_end().to_string());
+                }
+                requests.push(lines.join("\n"));
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (port, server)
+    }
+
+    fn respond(status: &str, headers: &[&str], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {}\r\n", status);
+        for header in headers {
+            response.push_str(&format!("{}\r\n", header));
+        }
+        format!("{}\r\n{}", response, body)
+    }
+
+    #[test]
+    fn parses_urls() {
+        let url = Url::parse("https://api.openweathermap.org/data/2.5/weather?q=x").unwrap();
+        assert!(url.tls);
+        assert_eq!(url.host, "api.openweathermap.org");
+        assert_eq!(url.port, 443);
+        assert_eq!(url.path, "/data/2.5/weather?q=x");
+
+        let url = Url::parse("http://localhost:8080?q=x").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("localhost", 8080));
+        assert_eq!(url.path, "/?q=x");
+        assert_eq!(url.host_header(), "localhost:8080");
+
+        assert_eq!(Url::parse("http://example.com").unwrap().path, "/");
+        assert!(Url::parse("ftp://example.com").is_err());
+        assert!(Url::parse("http://:80/").is_err());
+        assert!(Url::parse("http://example.com:http/").is_err());
+    }
+
+    #[test]
+    fn parses_ipv6_hosts() {
+        let url = Url::parse("http://[::1]:8080/data").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 8080));
+        assert_eq!(url.path, "/data");
+        assert_eq!(url.host_header(), "[::1]:8080");
+
+        let url = Url::parse("https://[2001:db8::1]/").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("2001:db8::1", 443));
+        assert_eq!(url.host_header(), "[2001:db8::1]");
+
+        assert!(Url::parse("http://[::1/").is_err());
+        assert!(Url::parse("http://[::1]8080/").is_err());
+    }
+
+    #[test]
+    fn reads_content_length() {
+        let raw = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Other: a:b\r\n\r\nhello, more";
+        let response = read_response(raw.as_bytes()).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+        assert_eq!(response.header("x-other"), Some("a:b"));
+        assert!(response.is_success());
+
+        let short = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        assert!(matches!(
+            read_response(short.as_bytes()),
+            Err(HttpError::Malformed("body shorter than announced"))
+        ));
+        let invalid = "HTTP/1.1 200 OK\r\nContent-Length: five\r\n\r\nhello";
+        assert!(matches!(
+            read_response(invalid.as_bytes()),
+            Err(HttpError::Malformed("invalid content length"))
+        ));
+    }
+
+    #[test]
+    fn reads_until_close_without_length() {
+        let raw = "HTTP/1.0 404 Not Found\r\n\r\nnothing here";
+        let response = read_response(raw.as_bytes()).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body, b"nothing here");
+        assert!(!response.is_success());
+    }
+
+    #[test]
+    fn reads_chunked() {
+        let raw = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: x\r\n\r\n";
+        let response = read_response(raw.as_bytes()).unwrap();
+        assert_eq!(response.body, b"hello, world");
+
+        // the connection may close right after the last chunk
+        let mut reader = "3\r\nabc\r\n0\r\n".as_bytes();
+        assert_eq!(read_chunked(&mut reader).unwrap(), b"abc");
+
+        let mut reader = "3\r\nabcdef\r\n0\r\n\r\n".as_bytes();
+        assert!(matches!(
+            read_chunked(&mut reader),
+            Err(HttpError::Malformed("missing chunk terminator"))
+        ));
+        let mut reader = "x\r\nabc\r\n".as_bytes();
+        assert!(matches!(
+            read_chunked(&mut reader),
+            Err(HttpError::Malformed("invalid chunk size"))
+        ));
+        let mut reader = "3\r\nabc\r\nffffffffffffffff\r\nabc\r\n".as_bytes();
+        assert!(matches!(
+            read_chunked(&mut reader),
+            Err(HttpError::Malformed("body too large"))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_responses() {
+        for raw in ["", "garbage\r\n\r\n", "HTTP/1.1 200 OK\r\nno colon\r\n\r\n"] {
+            assert!(matches!(
+                read_response(raw.as_bytes()),
+                Err(HttpError::Malformed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn gets_from_a_stub_server() {
+        let (port, server) = serve(vec![respond(
+            "200 OK",
+            &["Content-Type: application/json", "Content-Length: 2"],
+            "{}",
+        )]);
+        let url = format!("http://127.0.0.1:{}/data/2.5/weather?q=Hildesheim", port);
+        let response = client(true).get(&url).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"{}");
+
+        let requests = server.join().unwrap();
+        let request = &requests[0];
+        assert!(request.starts_with("GET /data/2.5/weather?q=Hildesheim HTTP/1.1\n"));
+        assert!(request.contains(&format!("Host: 127.0.0.1:{}", port)));
+        assert!(request.contains("Connection: close"));
+    }
+
+    #[test]
+    fn follows_redirects() {
+        let (port, server) = serve(vec![
+            respond("301 Moved Permanently", &["Location: /moved"], ""),
+            respond("302 Found", &["Location: /final?x=1"], ""),
+            respond(
+                "200 OK",
+                &["Transfer-Encoding: chunked"],
+                "2\r\nok\r\n0\r\n\r\n",
+            ),
+        ]);
+        let response = client(true)
+            .get(&format!("http://127.0.0.1:{}/start", port))
+            .unwrap();
+        assert_eq!(response.body, b"ok");
+
+        let paths: Vec<String> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|r| r.split_whitespace().nth(1).unwrap().to_string())
+            .collect();
+        assert_eq!(paths, ["/start", "/moved", "/final?x=1"]);
+    }
+
+    #[test]
+    fn resolves_relative_redirects() {
+        let url = Url::parse("https://example.com/data/2.5/weather?q=x").unwrap();
+        let path = |location: &str| url.join(location).unwrap().path;
+        assert_eq!(path("forecast?q=y"), "/data/2.5/forecast?q=y");
+        assert_eq!(path("../3.0/weather"), "/data/3.0/weather");
+        assert_eq!(path("./"), "/data/2.5/");
+        assert_eq!(path(".."), "/data/");
+        assert_eq!(path("../../../.."), "/");
+        assert_eq!(path("?q=z"), "/data/2.5/weather?q=z");
+        assert_eq!(path("/other#top"), "/other");
+        assert_eq!(path("./a:b"), "/data/2.5/a:b");
+        assert_eq!(path(""), "/data/2.5/weather?q=x");
+
+        let other = url.join("//other.example:8443/x").unwrap();
+        assert!(other.tls);
+        assert_eq!((other.host.as_str(), other.port), ("other.example", 8443));
+        assert_eq!(other.path, "/x");
+        assert!(matches!(
+            url.join("ftp://example.com/"),
+            Err(HttpError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            url.join("mailto:a@b"),
+            Err(HttpError::InvalidUrl(_))
+        ));
+    }
+
+    #[test]
+    fn follows_relative_redirects() {
+        let (port, server) = serve(vec![
+            respond("302 Found", &["Location: moved?x=1"], ""),
+            respond("307 Temporary Redirect", &["Location: ../final"], ""),
+            respond("200 OK", &["Content-Length: 2"], "ok"),
+        ]);
+        let response = client(true)
+            .get(&format!("http://127.0.0.1:{}/dir/start", port))
+            .unwrap();
+        assert_eq!(response.body, b"ok");
+
+        let paths: Vec<String> = server
+            .join()
+            .unwrap()
+            .iter()
+            .map(|r| r.split_whitespace().nth(1).unwrap().to_string())
+            .collect();
+        assert_eq!(paths, ["/dir/start", "/dir/moved?x=1", "/final"]);
+    }
+
+    #[test]
+    fn gives_up_after_too_many_redirects() {
+        let redirect = respond("307 Temporary Redirect", &["Location: /again"], "");
+        let (port, server) = serve(vec![redirect; MAX_REDIRECTS as usize + 1]);
+        let result = client(true).get(&format!("http://127.0.0.1:{}/", port));
+        assert!(matches!(result, Err(HttpError::TooManyRedirects)));
+        server.join().unwrap();
+
+        let (port, server) = serve(vec![respond("302 Found", &[], "")]);
+        let result = client(true).get(&format!("http://127.0.0.1:{}/", port));
+        assert!(matches!(
+            result,
+            Err(HttpError::Malformed("redirect without location"))
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn refuses_plain_http_unless_allowed() {
+        let result = client(false).get("http://127.0.0.1:1/");
+        assert!(matches!(result, Err(HttpError::InsecureUrl(host)) if host == "127.0.0.1"));
+    }
+
+    #[test]
+    fn reports_refused_connections() {
+        // bound and dropped again, nobody listens there any more
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let result = client(true).get(&format!("http://127.0.0.1:{}/", port));
+        assert!(matches!(result, Err(HttpError::Io(_))));
+    }
+}
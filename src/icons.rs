@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use crate::openweather::Weather;
+
+/* Maps OpenWeather conditions to the icon set in icons/, which has one image per icon code
+ * (https://openweathermap.org/weather-conditions): 01d.png ... 50n.png.
+ */
+
+const CODES: [&str; 9] = ["01", "02", "03", "04", "09", "10", "11", "13", "50"];
+
+// shown for anything we can't map, a plain cloud is the least misleading
+const FALLBACK: &str = "03d";
+
+// icon code for a condition id, see the table on the page above
+fn code_for_id(id: u32) -> Option<&'static str> {
+    match id {
+        200..=299 => Some("11"),
+        300..=399 => Some("09"),
+        500..=504 => Some("10"),
+        511 => Some("13"),
+        520..=599 => Some("09"),
+        600..=699 => Some("13"),
+        700..=799 => Some("50"),
+        800 => Some("01"),
+        801 => Some("02"),
+        802 => Some("03"),
+        803 | 804 => Some("04"),
+        _ => None,
+    }
+}
+
+/* name of the icon for a condition without extension. the `icon` field is used if it's one we
+ * ship, otherwise we derive it from the condition `id`, keeping day/night if the icon told us.
+ */
+pub fn icon_name(weather: &Weather) -> String {
+    let icon = weather.icon.as_str();
+    let code = icon.get(..2).unwrap_or("");
+    let variant = icon.get(2..).unwrap_or("");
+    if CODES.contains(&code) && (variant == "d" || variant == "n") {
+        return icon.to_string();
+    }
+
+    let variant = if variant == "n" { "n" } else { "d" };
+    match code_for_id(weather.id) {
+        Some(code) => format!("{}{}", code, variant),
+        None => FALLBACK.to_string(),
+    }
+}
+
+/* path of the icon for a condition in `dir`, falls back if the image is missing there */
+pub fn icon_path(dir: &Path, weather: &Weather) -> PathBuf {
+    let path = dir.join(format!("{}.png", icon_name(weather)));
+    if path.exists() {
+        path
+    } else {
+        dir.join(format!("{}.png", FALLBACK))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather(id: u32, icon: &str) -> Weather {
+        Weather {
+            id,
+            main: String::new(),
+            description: String::new(),
+            icon: icon.to_string(),
+        }
+    }
+
+    #[test]
+    fn takes_known_icons() {
+        assert_eq!(icon_name(&weather(500, "10d")), "10d");
+        // the icon wins over the id
+        assert_eq!(icon_name(&weather(800, "13n")), "13n");
+    }
+
+    #[test]
+    fn derives_unknown_icons_from_the_id() {
+        assert_eq!(icon_name(&weather(502, "12d")), "10d");
+        assert_eq!(icon_name(&weather(511, "")), "13d");
+        assert_eq!(icon_name(&weather(781, "10x")), "50d");
+        // keeping the night of the icon
+        assert_eq!(icon_name(&weather(801, "99n")), "02n");
+        assert_eq!(icon_name(&weather(804, "n")), "04d");
+    }
+
+    #[test]
+    fn falls_back_to_a_cloud() {
+        assert_eq!(icon_name(&weather(0, "")), FALLBACK);
+        assert_eq!(icon_name(&weather(950, "99n")), "03d");
+
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("icons");
+        assert_eq!(icon_path(&dir, &weather(200, "11n")), dir.join("11n.png"));
+        assert_eq!(icon_path(&dir, &weather(0, "xx")), dir.join("03d.png"));
+        // an icon set without the image
+        let empty = Path::new("/nonexistent");
+        assert_eq!(
+            icon_path(empty, &weather(200, "11n")),
+            empty.join("03d.png")
+        );
+    }
+}
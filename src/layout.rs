@@ -0,0 +1,557 @@
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::gui::Drawable;
+use crate::region::Clip;
+use crate::screen::Canvas;
+use crate::util::Point;
+
+/* Containers that work out where their children go, so objects don't need pixel positions.
+ * Laying out takes two passes: `measure` asks an object how much room it wants out of what is
+ * available, `arrange` tells it where it ended up and how big it is. containers measure their
+ * children to answer the first and arrange them in turn in the second.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Size {
+    pub fn new(width: u32, height: u32) -> Size {
+        Size { width, height }
+    }
+
+    // what is left after taking `width` and `height` away, nothing if that is more than there is
+    pub fn shrink(&self, width: u32, height: u32) -> Size {
+        Size::new(
+            self.width.saturating_sub(width),
+            self.height.saturating_sub(height),
+        )
+    }
+}
+
+/* room between the edge of a container and its children */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Padding {
+    pub fn all(padding: u32) -> Padding {
+        Padding {
+            top: padding,
+            right: padding,
+            bottom: padding,
+            left: padding,
+        }
+    }
+
+    fn horizontal(&self) -> u32 {
+        self.left + self.right
+    }
+
+    fn vertical(&self) -> u32 {
+        self.top + self.bottom
+    }
+}
+
+/* where a child goes in the room it is given along one axis */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    #[default]
+    Start,
+    Center,
+    End,
+    // takes all of the room, objects with a size of their own stay at the start
+    Stretch,
+}
+
+impl Anchor {
+    // offset and length of a child that wants `wanted` out of `room`
+    fn fit(&self, room: u32, wanted: u32) -> (u32, u32) {
+        let wanted = wanted.min(room);
+        match self {
+            Anchor::Start => (0, wanted),
+            Anchor::Center => ((room - wanted) / 2, wanted),
+            Anchor::End => (room - wanted, wanted),
+            Anchor::Stretch => (0, room),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // left to right
+    Horizontal,
+    // top to bottom
+    Vertical,
+}
+
+/* children one after the other, each as long as it wants to be, with `gap` pixels between them.
+ * across the stack they are put as `anchor` says
+ */
+pub struct Stack {
+    pub direction: Direction,
+    pub gap: u32,
+    pub padding: Padding,
+    pub anchor: Anchor,
+    children: Vec<Box<dyn Drawable>>,
+    // where `arrange` put the children, they are cut off at its edges
+    slots: Vec<(Point, Size)>,
+}
+
+impl Stack {
+    pub fn horizontal(gap: u32) -> Stack {
+        Stack::new(Direction::Horizontal, gap)
+    }
+
+    pub fn vertical(gap: u32) -> Stack {
+        Stack::new(Direction::Vertical, gap)
+    }
+
+    fn new(direction: Direction, gap: u32) -> Stack {
+        Stack {
+            direction,
+            gap,
+            padding: Padding::default(),
+            anchor: Anchor::Start,
+            children: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, child: Box<dyn Drawable>) {
+        self.children.push(child);
+    }
+
+    // (along, across) the stack
+    fn split(&self, size: Size) -> (u32, u32) {
+        match self.direction {
+            Direction::Horizontal => (size.width, size.height),
+            Direction::Vertical => (size.height, size.width),
+        }
+    }
+
+    fn join(&self, along: u32, across: u32) -> Size {
+        match self.direction {
+            Direction::Horizontal => Size::new(along, across),
+            Direction::Vertical => Size::new(across, along),
+        }
+    }
+
+    /* sizes of the children in `inner`, every child gets what the ones before it left */
+    fn measure_children(&self, inner: Size) -> Vec<Size> {
+        let (mut left, across) = self.split(inner);
+        let mut sizes = Vec::new();
+        for child in &self.children {
+            let size = child.measure(self.join(left, across));
+            left = left.saturating_sub(self.split(size).0 + self.gap);
+            sizes.push(size);
+        }
+        sizes
+    }
+}
+
+impl Drawable for Stack {
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        draw_clipped(&self.children, &self.slots, canvas)
+    }
+
+    fn measure(&self, available: Size) -> Size {
+        let p = &self.padding;
+        let sizes = self.measure_children(available.shrink(p.horizontal(), p.vertical()));
+        let gaps = self.gap * sizes.len().saturating_sub(1) as u32;
+        let along = sizes.iter().map(|s| self.split(*s).0).sum::<u32>() + gaps;
+        let across = sizes.iter().map(|s| self.split(*s).1).max().unwrap_or(0);
+        let size = self.join(along, across);
+        Size::new(size.width + p.horizontal(), size.height + p.vertical())
+    }
+
+    fn arrange(&mut self, pos: Point, size: Size) {
+        let p = self.padding;
+        let inner = size.shrink(p.horizontal(), p.vertical());
+        let sizes = self.measure_children(inner);
+        let (mut left, room) = self.split(inner);
+        let mut along = 0;
+        let mut slots = Vec::new();
+        for size in sizes {
+            let (wanted_along, wanted_across) = self.split(size);
+            let length = wanted_along.min(left);
+            let (offset, across) = self.anchor.fit(room, wanted_across);
+            let (x, y) = match self.direction {
+                Direction::Horizontal => (along, offset),
+                Direction::Vertical => (offset, along),
+            };
+            slots.push((
+                pos + Point::new(p.left + x, p.top + y),
+                self.join(length, across),
+            ));
+            along += length + self.gap;
+            left = left.saturating_sub(length + self.gap);
+        }
+        for (child, (pos, size)) in self.children.iter_mut().zip(&slots) {
+            child.arrange(*pos, *size);
+        }
+        self.slots = slots;
+    }
+}
+
+/* how wide a column of a grid is */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Track {
+    Px(u32),
+    // a share of what the fixed columns leave, relative to the weights of the others
+    Weight(f32),
+}
+
+/* children in rows of as many cells as there are columns, filled left to right and top to
+ * bottom. a row is as high as its highest child, `gap` pixels are between the rows and columns
+ */
+pub struct Grid {
+    pub columns: Vec<Track>,
+    // None takes all the width it may have
+    pub width: Option<u32>,
+    pub gap: u32,
+    pub padding: Padding,
+    // of the children in their cells
+    pub halign: Anchor,
+    pub valign: Anchor,
+    children: Vec<Box<dyn Drawable>>,
+    // where `arrange` put the children, they are cut off at its edges
+    slots: Vec<(Point, Size)>,
+}
+
+impl Grid {
+    pub fn new(columns: Vec<Track>, gap: u32) -> Grid {
+        Grid {
+            columns,
+            width: None,
+            gap,
+            padding: Padding::default(),
+            halign: Anchor::Start,
+            valign: Anchor::Start,
+            children: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    // `count` columns of the same width
+    pub fn even(count: usize, gap: u32) -> Grid {
+        Grid::new(vec![Track::Weight(1.0); count.max(1)], gap)
+    }
+
+    pub fn add(&mut self, child: Box<dyn Drawable>) {
+        self.children.push(child);
+    }
+
+    // of the columns when the grid is `width` wide, padding left out
+    pub fn column_widths(&self, width: u32) -> Vec<u32> {
+        let gaps = self.gap * self.columns.len().saturating_sub(1) as u32;
+        let fixed: u32 = self
+            .columns
+            .iter()
+            .map(|c| match c {
+                Track::Px(px) => *px,
+                Track::Weight(_) => 0,
+            })
+            .sum();
+        let weights: f32 = self
+            .columns
+            .iter()
+            .map(|c| match c {
+                Track::Px(_) => 0.0,
+                Track::Weight(w) => *w,
+            })
+            .sum();
+        let flexible = width.saturating_sub(fixed + gaps) as f32;
+        self.columns
+            .iter()
+            .map(|c| match c {
+                Track::Px(px) => *px,
+                Track::Weight(w) if weights > 0.0 => (flexible * w / weights) as u32,
+                Track::Weight(_) => 0,
+            })
+            .collect()
+    }
+
+    /* sizes of the children in the columns and the height of every row */
+    fn measure_children(&self, inner: Size) -> (Vec<u32>, Vec<Size>, Vec<u32>) {
+        let widths = self.column_widths(inner.width);
+        let mut sizes = Vec::new();
+        let mut rows = Vec::new();
+        let mut left = inner.height;
+        for row in self.children.chunks(widths.len()) {
+            let row_sizes: Vec<Size> = row
+                .iter()
+                .zip(&widths)
+                .map(|(child, width)| child.measure(Size::new(*width, left)))
+                .collect();
+            let height = row_sizes.iter().map(|s| s.height).max().unwrap_or(0);
+            left = left.saturating_sub(height + self.gap);
+            rows.push(height);
+            sizes.extend(row_sizes);
+        }
+        (widths, sizes, rows)
+    }
+}
+
+impl Drawable for Grid {
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        draw_clipped(&self.children, &self.slots, canvas)
+    }
+
+    // the columns share the width
+    fn measure(&self, available: Size) -> Size {
+        let p = &self.padding;
+        let width = self.width.unwrap_or(available.width);
+        let inner = Size::new(width, available.height).shrink(p.horizontal(), p.vertical());
+        let (_, _, rows) = self.measure_children(inner);
+        let gaps = self.gap * rows.len().saturating_sub(1) as u32;
+        Size::new(width, rows.iter().sum::<u32>() + gaps + p.vertical())
+    }
+
+    fn arrange(&mut self, pos: Point, size: Size) {
+        let p = self.padding;
+        let inner = size.shrink(p.horizontal(), p.vertical());
+        let (widths, sizes, rows) = self.measure_children(inner);
+        let mut y = p.top;
+        let columns = widths.len();
+        self.slots.clear();
+        for (r, height) in rows.iter().enumerate() {
+            // rows and columns past the edge of the grid get what is left, which may be nothing
+            let height = (*height).min((p.top + inner.height).saturating_sub(y));
+            let mut x = p.left;
+            for (c, width) in widths.iter().enumerate() {
+                let i = r * columns + c;
+                if i >= self.children.len() {
+                    break;
+                }
+                let cell = (*width).min((p.left + inner.width).saturating_sub(x));
+                let (dx, w) = self.halign.fit(cell, sizes[i].width);
+                let (dy, h) = self.valign.fit(height, sizes[i].height);
+                let slot = (pos + Point::new(x + dx, y + dy), Size::new(w, h));
+                self.children[i].arrange(slot.0, slot.1);
+                self.slots.push(slot);
+                x += width + self.gap;
+            }
+            y += height + self.gap;
+        }
+    }
+}
+
+/* draws `children`, each cut off at the slot it was arranged in. children that got no room or
+ * were put past the edge of the canvas aren't drawn at all
+ */
+fn draw_clipped(
+    children: &[Box<dyn Drawable>],
+    slots: &[(Point, Size)],
+    canvas: &dyn Canvas,
+) -> Result<(), Error> {
+    for (i, child) in children.iter().enumerate() {
+        match slots.get(i) {
+            Some((pos, size))
+                if size.width == 0
+                    || size.height == 0
+                    || pos.x >= canvas.width()
+                    || pos.y >= canvas.height() => {}
+            Some((pos, size)) => child.draw(&Clip::new(canvas, *pos, *size))?,
+            // never arranged, it is where it was made
+            None => child.draw(canvas)?,
+        }
+    }
+    Ok(())
+}
+
+/* empty room of a fixed size, e.g. to keep a cell or the start of a row free */
+pub struct Spacer {
+    size: Size,
+}
+
+impl Spacer {
+    pub fn new(width: u32, height: u32) -> Box<Spacer> {
+        Box::new(Spacer {
+            size: Size::new(width, height),
+        })
+    }
+}
+
+impl Drawable for Spacer {
+    fn draw(&self, _canvas: &dyn Canvas) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn measure(&self, _available: Size) -> Size {
+        self.size
+    }
+
+    fn arrange(&mut self, _pos: Point, _size: Size) {}
+}
+
+/* measures `object` in `available` and puts it at `pos` with the size it wants, returns that */
+pub fn place(object: &mut dyn Drawable, pos: Point, available: Size) -> Size {
+    let size = object.measure(available);
+    let size = Size::new(
+        size.width.min(available.width),
+        size.height.min(available.height),
+    );
+    object.arrange(pos, size);
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::MemoryDisplay;
+    use crate::fonts::{FontRegistry, DEFAULT_FONT};
+    use crate::gui::{Text, TextBox};
+    use crate::region::Region;
+    use crate::screen::{Rotation, Screen};
+    use crate::util::{Color, FontSetting};
+
+    /* a black box that keeps its size wherever it is put, like a text that is too long */
+    struct Block {
+        pos: Point,
+        size: Size,
+    }
+
+    fn block(width: u32, height: u32) -> Box<Block> {
+        Box::new(Block {
+            pos: Point::new(0, 0),
+            size: Size::new(width, height),
+        })
+    }
+
+    impl Drawable for Block {
+        fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+            let (width, height) = (self.size.width, self.size.height);
+            canvas.fill_rect(self.pos, width, height, Color::gray(0))
+        }
+
+        fn measure(&self, _available: Size) -> Size {
+            self.size
+        }
+
+        fn arrange(&mut self, pos: Point, _size: Size) {
+            self.pos = pos;
+        }
+    }
+
+    /* the black pixels of a screen of 60x40 after drawing `object` on it, as one string per row */
+    fn render(object: &dyn Drawable) -> Vec<String> {
+        let screen = Screen::with_display(MemoryDisplay::new(60, 40), Rotation::R0);
+        object.draw(&screen).unwrap();
+        screen.update().unwrap();
+        let fb = screen.fb.borrow();
+        (0..40)
+            .map(|y| {
+                (0..60)
+                    .map(|x| match fb.pixel(Point::new(x, y))[0] {
+                        0 => '#',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // the first and last column with black pixels in `row`
+    fn extent(row: &str) -> Option<(usize, usize)> {
+        Some((row.find('#')?, row.rfind('#')?))
+    }
+
+    #[test]
+    fn stacks_in_order() {
+        let mut stack = Stack::vertical(2);
+        stack.padding = Padding::all(1);
+        stack.anchor = Anchor::Center;
+        stack.add(block(10, 3));
+        stack.add(block(20, 4));
+        assert_eq!(stack.measure(Size::new(60, 40)), Size::new(22, 11));
+
+        place(&mut stack, Point::new(5, 5), Size::new(30, 20));
+        let rows = render(&stack);
+        assert_eq!(extent(&rows[6]), Some((11, 20)));
+        assert_eq!(extent(&rows[11]), Some((6, 25)));
+        assert_eq!(rows.iter().filter(|r| r.contains('#')).count(), 7);
+    }
+
+    #[test]
+    fn cuts_off_children_at_their_slot() {
+        // the second block wants 30 of the 10 pixels that are left, and is higher than the row
+        let mut stack = Stack::horizontal(0);
+        stack.add(block(30, 10));
+        stack.add(block(30, 20));
+        assert_eq!(stack.measure(Size::new(40, 10)), Size::new(60, 20));
+        stack.arrange(Point::new(0, 0), Size::new(40, 10));
+
+        let rows = render(&stack);
+        assert!(rows[..10].iter().all(|r| extent(r) == Some((0, 39))));
+        assert!(rows[10..].iter().all(|r| !r.contains('#')));
+    }
+
+    #[test]
+    fn cuts_off_grid_cells() {
+        let mut grid = Grid::even(2, 10);
+        grid.add(block(40, 5));
+        grid.add(block(5, 5));
+        grid.add(block(5, 30));
+        place(&mut grid, Point::new(0, 0), Size::new(50, 20));
+
+        let rows = render(&grid);
+        // 20 wide columns at 0 and 30
+        assert_eq!(extent(&rows[0]), Some((0, 34)));
+        assert_eq!(&rows[0][20..30], "..........");
+        // the second row gets what the first one and the gap left
+        assert_eq!(extent(&rows[15]), Some((0, 4)));
+        assert!(!rows[20].contains('#'));
+    }
+
+    #[test]
+    fn leaves_out_text_past_the_region() {
+        let fonts = FontRegistry::default();
+        let font = FontSetting {
+            name: DEFAULT_FONT.to_string(),
+            size: 14.0,
+            saturation: 1.0,
+        };
+        let long = "light rain and a thunderstorm with heavy drizzle later on";
+        let text = |text: &str, bounds: &TextBox| {
+            Text::layout(&fonts, text, Point::new(0, 0), font.clone(), bounds).unwrap()
+        };
+
+        // the description takes all of the region, the temperature starts below it
+        let mut stack = Stack::vertical(2);
+        stack.add(text(long, &TextBox::paragraph(60)));
+        stack.add(text("20°", &TextBox::line(60)));
+        place(&mut stack, Point::new(0, 0), Size::new(60, 20));
+        let mut region = Region::new(Point::new(0, 10), 60, 20, false);
+        region.add_object(Box::new(stack));
+        let rows = render(&region);
+        assert!(rows[30..].iter().all(|r| !r.contains('#')));
+
+        let mut grid = Grid::even(1, 2);
+        grid.add(text(long, &TextBox::paragraph(60)));
+        grid.add(text("20°", &TextBox::line(60)));
+        place(&mut grid, Point::new(0, 0), Size::new(60, 20));
+        let mut region = Region::new(Point::new(0, 10), 60, 20, false);
+        region.add_object(Box::new(grid));
+        let rows = render(&region);
+        assert!(rows[30..].iter().all(|r| !r.contains('#')));
+    }
+
+    #[test]
+    fn cuts_off_objects_at_the_region() {
+        let mut region = Region::new(Point::new(10, 10), 20, 15, false);
+        region.add_object(block(50, 50));
+        let rows = render(&region);
+        assert!(!rows[9].contains('#'));
+        assert!(rows[10..25].iter().all(|r| extent(r) == Some((10, 29))));
+        assert!(!rows[25].contains('#'));
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Locale, NaiveDate, Offset, Utc};
+use chrono_tz::Tz;
+
+/* Turns the unix timestamps of the API into the wall clock time of the location. OpenWeather
+ * sends the current UTC offset of the location with every response and that is used unless a
+ * zone is configured. a configured zone also gets a DST change within the forecast right, which a
+ * single offset can't.
+ */
+#[derive(Debug, Clone)]
+pub struct LocalTime {
+    // IANA zone like "Europe/Berlin", wins over the offset of the response
+    pub zone: Option<Tz>,
+    // language of day and month names
+    pub locale: Locale,
+    // strftime format of date and time, e.g. the observation time in the top bar
+    pub format: String,
+    // strftime format of a time of day, e.g. sunrise
+    pub time_format: String,
+}
+
+pub const DEFAULT_LOCALE: &str = "en_US";
+pub const DEFAULT_FORMAT: &str = "%a, %d. %B %Y %H:%M";
+pub const DEFAULT_TIME_FORMAT: &str = "%H:%M";
+
+// an invalid format only shows up when formatting, and then panics in to_string()
+fn check_format(format: &str, locale: Locale) -> Result<(), String> {
+    if StrftimeItems::new_with_locale(format, locale).any(|item| item == Item::Error) {
+        return Err(format!("invalid strftime format \"{}\"", format));
+    }
+    Ok(())
+}
+
+impl LocalTime {
+    pub fn new(
+        zone: Option<&str>,
+        locale: &str,
+        format: String,
+        time_format: String,
+    ) -> Result<LocalTime, String> {
+        let zone = match zone {
+            Some(name) => Some(
+                name.parse::<Tz>()
+                    .map_err(|_| format!("unknown time zone \"{}\"", name))?,
+            ),
+            None => None,
+        };
+        let locale = Locale::try_from(locale)
+            .map_err(|_| format!("unknown locale \"{}\", expected e.g. de_DE", locale))?;
+        check_format(&format, locale)?;
+        check_format(&time_format, locale)?;
+
+        Ok(LocalTime {
+            zone,
+            locale,
+            format,
+            time_format,
+        })
+    }
+
+    /* `offset` is the shift from UTC in seconds the API reported for the location */
+    pub fn at(&self, unix: i64, offset: i32) -> DateTime<FixedOffset> {
+        let utc = DateTime::<Utc>::from_timestamp(unix, 0).unwrap_or_default();
+        match self.zone {
+            Some(zone) => utc.with_timezone(&zone).fixed_offset(),
+            // offsets of a day or more are garbage, fall back to UTC
+            None => utc.with_timezone(&FixedOffset::east_opt(offset).unwrap_or(Utc.fix())),
+        }
+    }
+
+    pub fn format(&self, unix: i64, offset: i32, format: &str) -> String {
+        self.at(unix, offset)
+            .format_localized(format, self.locale)
+            .to_string()
+    }
+
+    pub fn datetime(&self, unix: i64, offset: i32) -> String {
+        self.format(unix, offset, &self.format)
+    }
+
+    pub fn time(&self, unix: i64, offset: i32) -> String {
+        self.format(unix, offset, &self.time_format)
+    }
+
+    pub fn date(&self, date: NaiveDate, format: &str) -> String {
+        date.format_localized(format, self.locale).to_string()
+    }
+}
+
+impl Default for LocalTime {
+    fn default() -> Self {
+        LocalTime {
+            zone: None,
+            locale: Locale::en_US,
+            format: DEFAULT_FORMAT.to_string(),
+            time_format: DEFAULT_TIME_FORMAT.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-10-14 15:51 UTC, a Monday
+    const MONDAY: i64 = 1728921060;
+
+    fn local_time(zone: Option<&str>, locale: &str) -> LocalTime {
+        LocalTime::new(zone, locale, "%a %H:%M %z".to_string(), "%H:%M".to_string()).unwrap()
+    }
+
+    #[test]
+    fn refuses_invalid_settings() {
+        let new = |zone: Option<&str>, locale: &str, format: &str| {
+            LocalTime::new(zone, locale, format.to_string(), "%H:%M".to_string()).unwrap_err()
+        };
+        assert_eq!(
+            new(Some("Mars/Olympus"), "en_US", "%H"),
+            "unknown time zone \"Mars/Olympus\""
+        );
+        assert_eq!(
+            new(None, "xx_XX", "%H"),
+            "unknown locale \"xx_XX\", expected e.g. de_DE"
+        );
+        assert_eq!(
+            new(None, "en_US", "%H:%Q"),
+            "invalid strftime format \"%H:%Q\""
+        );
+        assert!(check_format("%H:%M", Locale::en_US).is_ok());
+        assert!(check_format("%H:%", Locale::en_US).is_err());
+    }
+
+    #[test]
+    fn shifts_by_the_reported_offset() {
+        let time = local_time(None, "en_US");
+        // Newfoundland, three and a half hours behind
+        assert_eq!(time.datetime(MONDAY, -12600), "Mon 12:21 -0330");
+        assert_eq!(time.datetime(MONDAY, 19800), "Mon 21:21 +0530");
+        // a day or more is garbage
+        assert_eq!(time.datetime(MONDAY, 86400), "Mon 15:51 +0000");
+    }
+
+    #[test]
+    fn follows_daylight_saving_time() {
+        let time = local_time(Some("Europe/Berlin"), "en_US");
+        // summer time ends at 01:00 UTC on the 27th of October 2024, the clocks go back an hour
+        let before = 1729989000;
+        assert_eq!(time.datetime(before, 0), "Sun 02:30 +0200");
+        assert_eq!(time.datetime(before + 3600, 0), "Sun 02:30 +0100");
+        // the zone wins over the offset of the response
+        assert_eq!(time.datetime(before + 7200, 7200), "Sun 03:30 +0100");
+    }
+
+    #[test]
+    fn names_days_and_months_in_the_locale() {
+        let time = local_time(None, "de_DE");
+        let date = NaiveDate::from_ymd_opt(2024, 10, 14).unwrap();
+        assert_eq!(time.date(date, "%A, %d. %B"), "Montag, 14. Oktober");
+        assert_eq!(time.format(MONDAY, 7200, "%a %b"), "Mo Okt");
+
+        let time = local_time(None, "fr_FR");
+        assert_eq!(time.date(date, "%A %d %B"), "lundi 14 octobre");
+    }
+}
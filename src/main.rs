@@ -1,112 +1,403 @@
+mod bench;
+mod cache;
+mod config;
+mod daemon;
+mod dashboard;
+mod display;
+mod dither;
+mod draw;
+mod error;
+mod fonts;
 mod framebuffer;
 mod gui;
+mod http;
+mod icons;
+mod layout;
+mod localtime;
 mod openweather;
 mod region;
 mod screen;
 mod util;
 
-use gui::BoundingBox;
-
-use gui::{Image, Text};
-use openweather::get_weather;
+use cache::{Cache, Snapshot};
+use config::{Config, Fonts, Panel};
+use daemon::SystemClock;
+use dashboard::Dashboard;
+use display::{Display, MemoryDisplay};
+use error::Error;
+use fonts::{FontRegistry, DEFAULT_FONT};
+use framebuffer::{Framebuffer, Model};
+use gui::{Text, TextBox};
+use layout::{Size, Stack};
+use localtime::LocalTime;
+use openweather::get_report;
 use region::Region;
-use screen::Screen;
-use util::{FontSetting, Point};
-
-#[allow(unused)]
-const FONT_BIG: FontSetting = FontSetting {
-    name: "LucidaTypewriterRegular.ttf",
-    size: 192.0,
-    saturation: 1.0,
-};
-
-#[allow(unused)]
-const FONT_SMALL: FontSetting = FontSetting {
-    name: "LucidaTypewriterRegular.ttf",
-    size: 32.0,
-    saturation: 1.0,
-};
-
-#[allow(unused)]
-const FONT_MED: FontSetting = FontSetting {
-    name: "LucidaTypewriterRegular.ttf",
-    size: 96.0,
-    saturation: 1.0,
-};
-
-const MARGIN: u32 = 10;
-/* when we encounter an error, call this function. it will display a notification on the screen,
- * then exits the program. due to the nature of eink displays, the notification will stay on the
- * screen.
+use screen::{Rotation, Screen};
+use util::{unix_time, FontSetting, Point};
+
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+// resolution of the Kobo Aura One, used when rendering without a device
+const PNG_WIDTH: u32 = 1404;
+const PNG_HEIGHT: u32 = 1872;
+
+/* when we encounter an error, call this function. it will display a notification on the screen
+ * saying what failed, when, and how old the last good data is. due to the nature of eink
+ * displays, the notification will stay on the screen until the next successful refresh.
  */
-//fn error_state(screen: &Screen) {
-//    screen.clear();
-//
-//    let mut region = Region::new(
-//        screen,
-//        Point::new(0, 0),
-//        screen.width - 1,
-//        screen.height - 1,
-//        false,
-//    );
-//
-//    let error_text = Text::new(
-//        "Error encountered! Stop.",
-//        Point::new(250, screen.height / 2),
-//        font_big,
-//    );
-//    region.add_object(error_text);
-//    let _ = region.render(); // tough luck if this errors too
-//    screen.update();
-//
-//    panic!("Stopping after error");
-//}
-
-fn main() -> std::io::Result<()> {
-    let screen = Screen::new().unwrap(); // just panic, this program is without screen useless
-
-    /*** Regions ***/
-    let mut topbar = Region::new(
-        Point::new(MARGIN, MARGIN),
-        screen.width - MARGIN * 2,
-        50,
-        true,
-    );
-    let mut today = Region::new(
-        topbar.below_of(10),
-        screen.width / 2 - MARGIN,
-        screen.height / 2 - MARGIN * 2,
-        true,
+fn error_state<D: Display>(
+    screen: &Screen<D>,
+    registry: &FontRegistry,
+    fonts: &Fonts,
+    time: &LocalTime,
+    error: &Error,
+    last: Option<&Snapshot>,
+) {
+    // without a report or a configured zone we don't know the local time of the location, UTC it is
+    let offset = last.map_or(0, |s| s.report.current.timezone);
+    let format_time = |t: i64| time.datetime(t, offset);
+
+    let margin = 20;
+    let lines = [
+        (&fonts.med, "Error encountered!".to_string()),
+        (&fonts.small, error.to_string()),
+        (&fonts.small, format!("at {}", format_time(unix_time()))),
+        (
+            &fonts.small,
+            match last {
+                Some(s) => format!("last successful update {}", format_time(s.fetched_at)),
+                None => "no successful update yet".to_string(),
+            },
+        ),
+    ];
+
+    let mut region = Region::new(Point::new(0, 0), screen.width - 1, screen.height - 1, false);
+    let mut column = Stack::vertical(20);
+    let bounds = TextBox::paragraph(screen.width - margin * 2);
+    for (font, line) in lines {
+        // the configured font may be what is broken, the embedded one is always there
+        let embedded = FontSetting {
+            name: DEFAULT_FONT.to_string(),
+            ..font.clone()
+        };
+        let text = Text::layout(registry, &line, Point::new(0, 0), font.clone(), &bounds)
+            .or_else(|_| Text::layout(registry, &line, Point::new(0, 0), embedded, &bounds));
+        // tough luck if this errors too, show what we can
+        if let Ok(text) = text {
+            column.add(text);
+        }
+    }
+    let pos = Point::new(margin, screen.height / 3);
+    layout::place(
+        &mut column,
+        pos,
+        Size::new(bounds.width, screen.height - pos.y),
     );
-    /*** Fetch Weather Data ***/
-    let weather = get_weather().unwrap();
-
-    let temp = weather.main.temp;
-    let temp = format!("{:>2.1}", temp);
-    let country = weather.sys.country;
-    let city = weather.name;
-    let dt = weather.dt;
-    let datetime = chrono::NaiveDateTime::from_timestamp(dt, 0);
-    let datetime = datetime.format("%a, %d. %B %Y %H:%m:%S");
-    let localization = city + ", " + &country + ", " + &datetime.to_string();
-
-    /*** create GUI objects ***/
-    let localization = Text::new(&localization, Point::new(MARGIN, MARGIN), FONT_SMALL);
-    let temp = Text::new(&temp, Point::new(MARGIN, MARGIN), FONT_BIG);
-    let temp_icon = Image::new("icons/C.png", temp.right_of(10), 2.0);
-
-    /*** add GUI objects to regions ***/
-    topbar.add_object(localization);
-    today.add_object(temp);
-    today.add_object(temp_icon);
-
-    /*** add Regions to screen ***/
-    screen.add_region(topbar);
-    screen.add_region(today);
-    /*** draw ***/
+    region.add_object(Box::new(column));
+
+    screen.remove_regions();
     screen.clear();
-    screen.render();
-    screen.update();
+    screen.add_region(region);
+    let _ = screen.render();
+    let _ = screen.update();
+}
+
+const USAGE: &str =
+    "usage: kobors [--config <file>] [--daemon] [--bench] [--png <file> [--size <width>x<height>]]";
+
+/* the command line, see `main` */
+#[derive(Debug)]
+struct Args {
+    config: Option<String>,
+    png: Option<String>,
+    daemon: bool,
+    bench: bool,
+    size: (u32, u32),
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, Error> {
+    let mut parsed = Args {
+        config: None,
+        png: None,
+        daemon: false,
+        bench: false,
+        size: (PNG_WIDTH, PNG_HEIGHT),
+    };
+    let value = |args: &mut I, arg: &str| {
+        args.next()
+            .ok_or_else(|| Error::Usage(format!("{} expects a value", arg)))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => parsed.config = Some(value(&mut args, &arg)?),
+            "--daemon" => parsed.daemon = true,
+            "--bench" => parsed.bench = true,
+            "--png" => parsed.png = Some(value(&mut args, &arg)?),
+            "--size" => {
+                let size = value(&mut args, &arg)?;
+                parsed.size = size
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .filter(|&(w, h)| w > 0 && h > 0)
+                    .ok_or_else(|| {
+                        Error::Usage(format!("--size expects <width>x<height>, not {}", size))
+                    })?;
+            }
+            _ => return Err(Error::Usage(format!("unknown argument {}", arg))),
+        }
+    }
+    Ok(parsed)
+}
+
+/* the config is read from --config, $KOBORS_CONFIG or ./kobors.toml, in that order.
+ * with --daemon the weather is refreshed every refresh_interval seconds until SIGTERM/SIGINT,
+ * otherwise we draw once and exit.
+ * with --png the frame is rendered in memory and written to <file> instead of /dev/fb0, so
+ * layouts can be worked on without a Kobo.
+ * with --bench nothing is drawn, the time it takes to write whole frames to the display (or the
+ * memory one with --png) is printed instead.
+ */
+fn main() {
+    let Args {
+        config,
+        png,
+        daemon,
+        bench,
+        size,
+    } = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    // a broken config is shown on the screen like any other error, so load it only in run().
+    // the panel settings are needed for the screen though, we make do without them if need be
+    let config = Config::path(config);
+    let panel = Config::load(&config)
+        .map(|config| config.panel)
+        .unwrap_or_default();
+
+    let result = match png {
+        Some(_) if bench => {
+            bench::run(&mut MemoryDisplay::new(size.0, size.1));
+            Ok(())
+        }
+        Some(path) => {
+            let screen =
+                Screen::with_display(MemoryDisplay::new(size.0, size.1), rotation(&panel, None));
+            run(&screen, &config, daemon, |screen| {
+                let fb = screen.fb.borrow();
+                if let Err(e) = fb.save_png(&path) {
+                    eprintln!("cannot write {}: {}", path, e);
+                }
+            })
+        }
+        // this program is useless without a screen, nowhere to show the error but stderr
+        None => {
+            let model = panel
+                .model
+                .as_deref()
+                .and_then(Model::by_name)
+                .unwrap_or_else(Model::detect);
+            if bench {
+                match Framebuffer::new("/dev/fb0", model) {
+                    Ok(mut fb) => bench::run(&mut fb),
+                    Err(e) => eprintln!("cannot open /dev/fb0: {}", e),
+                }
+                return;
+            }
+            match Screen::new(model, rotation(&panel, Some(model))) {
+                Ok(screen) => run(&screen, &config, daemon, |_| {}),
+                Err(e) => Err(e),
+            }
+        }
+    };
 
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/* rotation from the config, or what `model` needs to be upright */
+fn rotation(panel: &Panel, model: Option<&Model>) -> Rotation {
+    let degrees = panel.rotation.or(model.map(|m| m.rotation)).unwrap_or(0);
+    Rotation::from_degrees(degrees).unwrap_or(Rotation::R0)
+}
+
+/* fetches a fresh report and caches it. if that fails, the cached report is returned instead */
+fn fetch(config: &Config, cache: &Cache) -> Result<Snapshot, Error> {
+    match get_report(config) {
+        Ok(report) => {
+            let snapshot = Snapshot::fresh(report);
+            if let Err(e) = cache.store(&snapshot) {
+                eprintln!("cannot write cache {}: {}", config.cache_file.display(), e);
+            }
+            Ok(snapshot)
+        }
+        Err(e) => {
+            eprintln!("fetching weather failed: {}", e);
+            cache.load().ok_or(e.into())
+        }
+    }
+}
+
+/* draws once, or keeps redrawing in daemon mode. `present` is called after every drawn frame.
+ * errors end up on the screen: in daemon mode we try again at the next refresh, otherwise the
+ * error is returned.
+ */
+fn run<D: Display, P: Fn(&Screen<D>)>(
+    screen: &Screen<D>,
+    config: &Path,
+    daemon: bool,
+    present: P,
+) -> Result<(), Error> {
+    let config = match Config::load(config) {
+        Ok(config) => config,
+        Err(e) => {
+            let e = e.into();
+            let registry = FontRegistry::default();
+            error_state(
+                screen,
+                &registry,
+                &Fonts::default(),
+                &LocalTime::default(),
+                &e,
+                None,
+            );
+            present(screen);
+            return Err(e);
+        }
+    };
+    screen.set_full_refresh_every(config.panel.full_refresh_every);
+    screen.set_partial_mode(config.panel.waveform.mode());
+    screen.set_dither(config.panel.dither);
+    let cache = Cache::new(&config.cache_file);
+    // lives as long as the daemon, every font is read only once
+    let registry = FontRegistry::new(&config.layout.font_paths, &config.layout.fallback_fonts);
+    let fail = |e: &Error| {
+        error_state(
+            screen,
+            &registry,
+            &config.layout.fonts,
+            &config.time,
+            e,
+            cache.load().as_ref(),
+        );
+        present(screen);
+    };
+    // the screen doesn't change size, the regions are placed and checked once
+    let dashboard = match Dashboard::load(
+        config.layout.file.as_deref(),
+        screen.width,
+        screen.height,
+        &config,
+        &registry,
+    ) {
+        Ok(dashboard) => dashboard,
+        Err(e) => {
+            fail(&e);
+            return Err(e);
+        }
+    };
+
+    if !daemon {
+        let result = fetch(&config, &cache)
+            .and_then(|snapshot| dashboard.draw(screen, &registry, &config, &snapshot));
+        match &result {
+            Ok(()) => present(screen),
+            Err(e) => fail(e),
+        }
+        return result;
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, stop.clone())
+        .map_err(Error::Signal)?;
+    signal_hook::flag::register(signal_hook::consts::SIGINT, stop.clone())
+        .map_err(Error::Signal)?;
+
+    daemon::run(
+        &SystemClock,
+        Duration::from_secs(config.refresh_interval),
+        &stop,
+        || fetch(&config, &cache),
+        |snapshot: &Snapshot| {
+            dashboard.draw(screen, &registry, &config, snapshot)?;
+            present(screen);
+            Ok(())
+        },
+        fail,
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        parse_args(line.split_whitespace().map(String::from)).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let args = parse("--config k.toml --png out.png --size 758x1024 --daemon").unwrap();
+        assert_eq!(args.config.as_deref(), Some("k.toml"));
+        assert_eq!(args.png.as_deref(), Some("out.png"));
+        assert_eq!(args.size, (758, 1024));
+        assert!(args.daemon && !args.bench);
+
+        let args = parse("").unwrap();
+        assert_eq!((args.config, args.png), (None, None));
+        assert_eq!(args.size, (PNG_WIDTH, PNG_HEIGHT));
+    }
+
+    #[test]
+    fn shows_errors_with_broken_fonts() {
+        let screen = Screen::with_display(MemoryDisplay::new(600, 800), Rotation::R0);
+        let registry = FontRegistry::new(&[], &["Missing.ttf".to_string()]);
+        let mut fonts = Fonts::default();
+        for font in [&mut fonts.big, &mut fonts.med, &mut fonts.small] {
+            font.name = "AlsoMissing.ttf".to_string();
+        }
+        let error = Error::Render("something broke");
+        error_state(
+            &screen,
+            &registry,
+            &fonts,
+            &LocalTime::default(),
+            &error,
+            None,
+        );
+
+        let fb = screen.fb.borrow();
+        let dark = (0..800)
+            .flat_map(|y| (0..600).map(move |x| Point::new(x, y)))
+            .filter(|p| fb.pixel(*p)[0] < 128)
+            .count();
+        assert!(dark > 1000, "{} dark pixels", dark);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(
+            parse("--verbose").unwrap_err(),
+            "unknown argument --verbose"
+        );
+        assert_eq!(parse("--config").unwrap_err(), "--config expects a value");
+        assert_eq!(parse("--png").unwrap_err(), "--png expects a value");
+        for size in ["758", "758x", "x1024", "0x1024", "-1x2"] {
+            assert_eq!(
+                parse(&format!("--png out.png --size {}", size)).unwrap_err(),
+                format!("--size expects <width>x<height>, not {}", size)
+            );
+        }
+    }
+}
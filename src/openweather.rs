@@ -1,33 +1,52 @@
+use crate::config::{Config, Location};
+use crate::http::{self, Client, HttpError, Response};
+use crate::localtime::LocalTime;
+use chrono::{NaiveDate, Timelike};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::io::prelude::*;
-use std::net::TcpStream;
-use std::str;
+use std::fmt;
+use std::time::Duration;
 
-#[derive(Serialize, Deserialize, Debug)]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/* Model of the current weather response, see https://openweathermap.org/current#fields_json.
+ * Everything the documentation marks as optional, or that is missing for some locations in
+ * practice (sea_level away from coasts, country over the ocean, gusts when it's calm), is an
+ * Option here, so one missing field doesn't cost us the whole response.
+ */
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Sys {
-    pub country: String,
-    pub sunrise: u32,
-    pub sunset: u32,
+    pub country: Option<String>,
+    pub sunrise: i64,
+    pub sunset: i64,
 }
-#[derive(Serialize, Deserialize, Debug)]
+
+// volume in mm, the current weather has the last hour, the forecast the last 3 hours
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Rain {
-    pub one_hour: f32,
-    pub three_hour: f32,
+    #[serde(rename = "1h")]
+    pub one_hour: Option<f32>,
+    #[serde(rename = "3h")]
+    pub three_hour: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Snow {
-    pub one_hour: f32,
-    pub three_hour: f32,
+    #[serde(rename = "1h")]
+    pub one_hour: Option<f32>,
+    #[serde(rename = "3h")]
+    pub three_hour: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Coord {
     pub lat: f32,
     pub lon: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Weather {
     pub id: u32,
     pub main: String,
@@ -35,59 +54,476 @@ pub struct Weather {
     pub icon: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Clouds {
     pub all: u32,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Main {
     pub temp: f32,
     pub feels_like: f32,
     pub temp_min: f32,
     pub temp_max: f32,
+    // hPa
     pub pressure: u16,
+    // %
     pub humidity: u16,
-    pub sea_level: u16,
-    pub grnd_level: u16,
+    pub sea_level: Option<u16>,
+    pub grnd_level: Option<u16>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Wind {
     pub speed: f32,
     pub deg: f32,
-    pub gust: f32,
+    pub gust: Option<f32>,
+}
+
+impl Wind {
+    // direction the wind comes from on a 16 point compass rose
+    pub fn cardinal(&self) -> &'static str {
+        const POINTS: [&str; 16] = [
+            "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+            "NW", "NNW",
+        ];
+        let sector = (self.deg.rem_euclid(360.0) / 22.5).round() as usize;
+        POINTS[sector % 16]
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct OpenWeather {
+    // city id, 0 for plain coordinates
+    #[serde(default)]
+    pub id: i64,
     pub name: String,
+    // status of the request, 200 for any response we get to parse
+    #[serde(default)]
+    pub cod: i32,
+    // internal parameter of the API, usually "stations"
+    #[serde(default)]
+    pub base: String,
     pub coord: Coord,
-    pub timezone: u16,
+    // shift from UTC in seconds, negative west of Greenwich
+    pub timezone: i32,
     pub weather: Vec<Weather>,
     pub wind: Wind,
     pub main: Main,
     pub clouds: Clouds,
-    pub visibility: i32,
+    // meters, capped at 10km
+    pub visibility: Option<i32>,
     pub rain: Option<Rain>,
     pub snow: Option<Snow>,
     pub dt: i64,
     pub sys: Sys,
 }
 
-pub fn get_weather() -> std::io::Result<OpenWeather> {
-    println!("Hello, world!");
+/*** 5 day / 3 hour forecast, /data/2.5/forecast ***/
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForecastEntry {
+    pub dt: i64,
+    pub main: Main,
+    pub weather: Vec<Weather>,
+    pub clouds: Clouds,
+    pub wind: Wind,
+    // probability of precipitation, 0 to 1
+    #[serde(default)]
+    pub pop: f32,
+    pub rain: Option<Rain>,
+    pub snow: Option<Snow>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct City {
+    pub name: String,
+    pub coord: Coord,
+    pub country: Option<String>,
+    // shift from UTC in seconds
+    pub timezone: i32,
+    pub sunrise: i64,
+    pub sunset: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub list: Vec<ForecastEntry>,
+    pub city: City,
+}
+
+/* the 3 hour slots of one local day boiled down to what fits in a forecast column */
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayForecast {
+    pub date: NaiveDate,
+    pub temp_min: f32,
+    pub temp_max: f32,
+    // condition of the slot closest to noon
+    pub weather: Weather,
+    // rain and snow in mm
+    pub precipitation: f32,
+    // highest probability of precipitation of the day
+    pub pop: f32,
+}
+
+impl Forecast {
+    /* the days are split at local midnight of the location, or of the zone in `time` */
+    pub fn daily(&self, time: &LocalTime) -> Vec<DayForecast> {
+        let mut days: Vec<DayForecast> = Vec::new();
+        // distance of the chosen icon's slot from noon, per day
+        let mut noon_distance: Vec<u32> = Vec::new();
+
+        for entry in &self.list {
+            let local = time.at(entry.dt, self.city.timezone);
+            let distance = (local.hour() as i32 - 12).unsigned_abs();
+            let weather = entry.weather.first();
+            let precipitation = entry
+                .rain
+                .as_ref()
+                .and_then(|r| r.three_hour)
+                .unwrap_or(0.0)
+                + entry
+                    .snow
+                    .as_ref()
+                    .and_then(|s| s.three_hour)
+                    .unwrap_or(0.0);
+
+            match days.last_mut() {
+                Some(day) if day.date == local.date_naive() => {
+                    day.temp_min = day.temp_min.min(entry.main.temp_min);
+                    day.temp_max = day.temp_max.max(entry.main.temp_max);
+                    day.precipitation += precipitation;
+                    day.pop = day.pop.max(entry.pop);
+                    let last = noon_distance.last_mut().unwrap();
+                    if let Some(weather) = weather {
+                        if distance < *last {
+                            day.weather = weather.clone();
+                            *last = distance;
+                        }
+                    }
+                }
+                _ => {
+                    days.push(DayForecast {
+                        date: local.date_naive(),
+                        temp_min: entry.main.temp_min,
+                        temp_max: entry.main.temp_max,
+                        weather: weather.cloned().unwrap_or_default(),
+                        precipitation,
+                        pop: entry.pop,
+                    });
+                    noon_distance.push(if weather.is_some() {
+                        distance
+                    } else {
+                        u32::MAX
+                    });
+                }
+            }
+        }
+        days
+    }
+}
+
+/* everything we show on screen, fetched in one go */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WeatherReport {
+    pub current: OpenWeather,
+    pub forecast: Forecast,
+}
+
+/* percent-encode everything except unreserved characters so city names like "Łódź" or
+ * "Frankfurt am Main" survive the query string
+ */
+fn encode(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn query(config: &Config) -> String {
+    let location = match &config.location {
+        Location::Coordinates { lat, lon } => format!("lat={}&lon={}", lat, lon),
+        Location::City(city) => format!("q={}", encode(city)),
+    };
+    format!(
+        "appid={}&{}&units={}&lang={}&mode=json",
+        encode(&config.api_key),
+        location,
+        config.units.as_query(),
+        encode(&config.lang)
+    )
+}
+
+/* everything that can go wrong between asking for the weather and having it parsed */
+#[derive(Debug)]
+pub enum WeatherError {
+    Http(HttpError),
+    // 401, the API key is wrong or not activated yet
+    Unauthorized(String),
+    // 404, OpenWeather doesn't know the configured city
+    NotFound(String),
+    // 429, the free plan allows 60 calls per minute
+    RateLimited(String),
+    // any other non-2xx status, with the message from the error body if there was one
+    Status(u16, String),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for WeatherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeatherError::Http(e) => write!(f, "{}", e),
+            WeatherError::Unauthorized(msg) => write!(f, "API key rejected: {}", msg),
+            WeatherError::NotFound(msg) => write!(f, "location not found: {}", msg),
+            WeatherError::RateLimited(msg) => write!(f, "rate limited: {}", msg),
+            WeatherError::Status(status, msg) => write!(f, "HTTP {}: {}", status, msg),
+            WeatherError::Parse(e) => write!(f, "cannot parse response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WeatherError {}
+
+impl From<HttpError> for WeatherError {
+    fn from(e: HttpError) -> Self {
+        WeatherError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for WeatherError {
+    fn from(e: serde_json::Error) -> Self {
+        WeatherError::Parse(e)
+    }
+}
+
+/* body OpenWeather sends along with error statuses, e.g. {"cod":401, "message": "Invalid API
+ * key. ..."}. `cod` is a number or a string depending on the endpoint, so we don't care about it.
+ */
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+fn check_status(response: &Response) -> Result<(), WeatherError> {
+    if response.is_success() {
+        return Ok(());
+    }
+
+    let message = match serde_json::from_slice::<ApiError>(&response.body) {
+        Ok(error) => error.message,
+        Err(_) => String::from_utf8_lossy(&response.body).trim().to_string(),
+    };
+    Err(match response.status {
+        401 => WeatherError::Unauthorized(message),
+        404 => WeatherError::NotFound(message),
+        429 => WeatherError::RateLimited(message),
+        status => WeatherError::Status(status, message),
+    })
+}
+
+fn fetch<T: DeserializeOwned>(config: &Config, endpoint: &str) -> Result<T, WeatherError> {
+    let tls = http::tls_config(config.ca_file.as_deref())?;
+    let client = Client::new(
+        CONNECT_TIMEOUT,
+        READ_TIMEOUT,
+        tls,
+        config.allow_insecure_http,
+    );
+    let url = format!(
+        "{}/data/2.5/{}?{}",
+        config.api_url.trim_end_matches('/'),
+        endpoint,
+        query(config)
+    );
+
+    let response = client.get(&url)?;
+    check_status(&response)?;
+
+    Ok(serde_json::from_slice(&response.body)?)
+}
+
+pub fn get_weather(config: &Config) -> Result<OpenWeather, WeatherError> {
+    fetch(config, "weather")
+}
+
+pub fn get_forecast(config: &Config) -> Result<Forecast, WeatherError> {
+    fetch(config, "forecast")
+}
+
+pub fn get_report(config: &Config) -> Result<WeatherReport, WeatherError> {
+    Ok(WeatherReport {
+        current: get_weather(config)?,
+        forecast: get_forecast(config)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // responses as the API sent them, trimmed to a few forecast entries
+    const NEW_YORK: &str = include_str!("../fixtures/weather_new_york.json");
+    const PACIFIC: &str = include_str!("../fixtures/weather_pacific.json");
+    const TROMSO: &str = include_str!("../fixtures/weather_tromso.json");
+    const KOLKATA: &str = include_str!("../fixtures/weather_kolkata.json");
+    const FORECAST: &str = include_str!("../fixtures/forecast_new_york.json");
+
+    fn weather(json: &str) -> OpenWeather {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn local_time(zone: Option<&str>) -> LocalTime {
+        LocalTime::new(zone, "en_US", "%c".to_string(), "%H:%M".to_string()).unwrap()
+    }
+
+    #[test]
+    fn parses_current_weather() {
+        let ny = weather(NEW_YORK);
+        assert_eq!(ny.name, "New York");
+        assert_eq!(ny.timezone, -14400);
+        assert_eq!(ny.sys.country.as_deref(), Some("US"));
+        assert_eq!(ny.main.sea_level, Some(1009));
+        assert_eq!(ny.wind.gust, Some(9.26));
+        assert_eq!(ny.wind.cardinal(), "ENE");
+        assert_eq!(ny.visibility, Some(8047));
+        let rain = ny.rain.as_ref().unwrap();
+        assert_eq!((rain.one_hour, rain.three_hour), (Some(0.51), None));
+        assert!(ny.snow.is_none());
+        // 11:51 in New York
+        assert_eq!(local_time(None).time(ny.dt, ny.timezone), "11:51");
+
+        let kolkata = weather(KOLKATA);
+        assert_eq!(kolkata.timezone, 19800);
+        assert_eq!(local_time(None).time(kolkata.dt, kolkata.timezone), "14:00");
+    }
+
+    #[test]
+    fn parses_without_optional_fields() {
+        let ocean = weather(PACIFIC);
+        assert_eq!(ocean.id, 0);
+        assert_eq!(ocean.name, "");
+        assert_eq!(ocean.sys.country, None);
+        assert_eq!((ocean.main.sea_level, ocean.main.grnd_level), (None, None));
+        assert_eq!(ocean.wind.gust, None);
+        assert_eq!(ocean.visibility, None);
+        assert!(ocean.rain.is_none() && ocean.snow.is_none());
+    }
+
+    #[test]
+    fn parses_rain_and_snow() {
+        let tromso = weather(TROMSO);
+        assert_eq!(tromso.name, "Tromsø");
+        assert_eq!(tromso.weather.len(), 2);
+        assert_eq!(tromso.main.temp, -3.1);
+        let snow = tromso.snow.as_ref().unwrap();
+        assert_eq!((snow.one_hour, snow.three_hour), (Some(0.42), Some(1.37)));
+        let rain = tromso.rain.as_ref().unwrap();
+        assert_eq!((rain.one_hour, rain.three_hour), (None, Some(0.12)));
+    }
+
+    #[test]
+    fn groups_forecast_by_local_day() {
+        let forecast: Forecast = serde_json::from_str(FORECAST).unwrap();
+        assert_eq!(forecast.list.len(), 12);
+        assert_eq!(forecast.city.timezone, -14400);
+
+        // the list starts at midnight UTC, which is the evening before in New York
+        let days = forecast.daily(&local_time(None));
+        let dates: Vec<String> = days.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(dates, ["2024-10-13", "2024-10-14", "2024-10-15"]);
+
+        let today = &days[1];
+        assert_eq!((today.temp_min, today.temp_max), (10.4, 16.5));
+        // 11:00 is the slot closest to noon
+        assert_eq!(today.weather.main, "Rain");
+        assert!((today.precipitation - 1.75).abs() < 1e-6);
+        assert_eq!(today.pop, 0.8);
+
+        let tomorrow = &days[2];
+        assert_eq!(tomorrow.weather.main, "Snow");
+        assert!((tomorrow.precipitation - 0.3).abs() < 1e-6);
+        assert_eq!(days[0].precipitation, 0.0);
+
+        // a configured zone splits the days at its own midnight
+        let days = forecast.daily(&local_time(Some("Europe/Berlin")));
+        let dates: Vec<String> = days.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(dates, ["2024-10-14", "2024-10-15"]);
+    }
+
+    fn response(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
 
-    let mut stream = TcpStream::connect("api.openweathermap.org:80")?;
+    #[test]
+    fn maps_error_statuses() {
+        assert!(check_status(&response(200, "{}")).is_ok());
 
-    stream.write("GET /data/2.5/weather?appid=0cface643ba4c958e2b8174fbbb7170a&lat=52.1508&lon=9.9511&units=metric&mode=json HTTP/1.0\r\n\r\n".as_bytes())?;
-    let mut buf: [u8; 4096] = [' ' as u8; 4096];
-    stream.read(&mut buf)?;
+        let body = r#"{"cod":401, "message": "Invalid API key."}"#;
+        assert!(matches!(
+            check_status(&response(401, body)),
+            Err(WeatherError::Unauthorized(msg)) if msg == "Invalid API key."
+        ));
+        let body = r#"{"cod":"404", "message": "city not found"}"#;
+        assert!(matches!(
+            check_status(&response(404, body)),
+            Err(WeatherError::NotFound(msg)) if msg == "city not found"
+        ));
+        // proxies in between answer with plain text
+        assert!(matches!(
+            check_status(&response(429, "slow down\n")),
+            Err(WeatherError::RateLimited(msg)) if msg == "slow down"
+        ));
+        assert!(matches!(
+            check_status(&response(503, "")),
+            Err(WeatherError::Status(503, msg)) if msg.is_empty()
+        ));
+    }
 
-    let response = str::from_utf8(&buf).unwrap();
-    let response: Option<(&str, &str)> = response.split_once("\r\n\r\n");
-    let response = response.unwrap().1.trim();
+    #[test]
+    fn fetches_from_the_configured_url() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let len = stream.read(&mut request).unwrap();
+            let body = r#"{"cod":401, "message": "Invalid API key."}"#;
+            write!(
+                stream,
+                "HTTP/1.1 401 Unauthorized\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
 
-    let response: OpenWeather = serde_json::from_str(&response)?;
+        let config = Config::parse(&format!(
+            "api_key = \"k&y\"\n\
+             api_url = \"http://127.0.0.1:{}/\"\n\
+             allow_insecure_http = true\n\
+             [location]\ncity = \"Hildesheim\"\n",
+            port
+        ))
+        .unwrap();
+        assert!(matches!(
+            get_weather(&config),
+            Err(WeatherError::Unauthorized(msg)) if msg == "Invalid API key."
+        ));
 
-    Ok(response)
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /data/2.5/weather?appid=k%26y&q=Hildesheim&"));
+    }
 }
@@ -1,9 +1,15 @@
-use crate::gui::{BoundingBox, Drawable};
-use crate::screen::Screen;
+use crate::draw::{Draw, Stroke, Style};
+use crate::error::Error;
+use crate::gui::Drawable;
+use crate::layout::Size;
+use crate::screen::Canvas;
 use crate::util::{Color, Point};
 
-pub struct Region<'a> {
-    pub screen: Option<&'a Screen<'a>>,
+/* A rectangle of the screen with objects in it, whose positions are relative to its top left
+ * corner. regions nest, a region added to another one as an object is drawn at its position
+ * inside of it.
+ */
+pub struct Region {
     pub pos: Point,
     pub width: u32,
     pub height: u32,
@@ -11,10 +17,9 @@ pub struct Region<'a> {
     pub objects: Vec<Box<dyn Drawable>>,
 }
 
-impl<'a> Region<'a> {
-    pub fn new(pos: Point, width: u32, height: u32, border: bool) -> Region<'a> {
+impl Region {
+    pub fn new(pos: Point, width: u32, height: u32, border: bool) -> Region {
         Region {
-            screen: None,
             pos,
             width,
             height,
@@ -26,42 +31,161 @@ impl<'a> Region<'a> {
         self.objects.push(obj);
     }
 
-    pub fn render(&self) -> Result<(), &str> {
+    pub fn render(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        let view = View {
+            canvas,
+            pos: self.pos,
+        };
         if self.border {
-            // no error checking because we already know from region creation that it's not out of
-            // bounds
-            for x in 0..self.width {
-                let _ = self.plot(Point::new(x, 0), Color::new(0, 0, 0));
-                let _ = self.plot(Point::new(x, self.height), Color::new(0, 0, 0));
-            }
-
-            for y in 0..self.height {
-                let _ = self.plot(Point::new(0, y), Color::new(0, 0, 0));
-                let _ = self.plot(Point::new(self.width, y), Color::new(0, 0, 0));
-            }
+            let stroke = Stroke::solid(1.0, Color::new(0, 0, 0));
+            view.rect(Point::new(0, 0), self.width, self.height, 0, &Style::outlined(stroke))?;
         }
 
+        // objects that don't fit are cut off at the edge instead of painting over the neighbours
+        let clip = Clip::new(&view, Point::new(0, 0), Size::new(self.width, self.height));
         for o in &self.objects {
-            match o.draw(self) {
-                Ok(_) => {}
-                Err(_) => return Err("render call out of screen"),
-            }
+            o.draw(&clip)?;
         }
         Ok(())
     }
+}
+
+impl Drawable for Region {
+    fn draw(&self, canvas: &dyn Canvas) -> Result<(), Error> {
+        self.render(canvas)
+    }
+
+    fn measure(&self, _available: Size) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    // a region in a container takes all the room it is given
+    fn arrange(&mut self, pos: Point, size: Size) {
+        self.pos = pos;
+        self.width = size.width;
+        self.height = size.height;
+    }
+}
+
+/* the canvas as the objects of a region see it, with the origin moved to the top left corner of
+ * the region. it reaches as far as the canvas below it, objects are only stopped at the screen edge
+ */
+struct View<'c> {
+    canvas: &'c dyn Canvas,
+    pos: Point,
+}
+
+impl Canvas for View<'_> {
+    fn width(&self) -> u32 {
+        self.canvas.width().saturating_sub(self.pos.x)
+    }
+
+    fn height(&self) -> u32 {
+        self.canvas.height().saturating_sub(self.pos.y)
+    }
 
-    pub fn plot(&self, px: Point, color: Color) -> Result<(), &str> {
-        let screen = self.screen.unwrap();
-        screen.plot(self.pos + px, color)
+    fn blend(&self, px: Point, color: Color, alpha: u8) -> Result<(), Error> {
+        self.canvas.blend(self.pos + px, color, alpha)
     }
+
+    fn blit_gray(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        self.canvas.blit_gray(self.pos + pos, width, height, pixels)
+    }
+
+    fn composite(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        self.canvas.composite(self.pos + pos, width, height, pixels)
+    }
+
+    fn fill_rect(&self, pos: Point, width: u32, height: u32, color: Color) -> Result<(), Error> {
+        self.canvas.fill_rect(self.pos + pos, width, height, color)
+    }
+}
+
+/* the part of a canvas from `pos` to `pos + size`, in the coordinates of the canvas. whatever is
+ * drawn outside of it is dropped, pictures are cropped to it
+ */
+pub struct Clip<'c> {
+    canvas: &'c dyn Canvas,
+    pos: Point,
+    size: Size,
 }
 
-impl<'a> BoundingBox for Region<'a> {
-    fn right_of(&self, margin: u32) -> Point {
-        self.pos + Point::new(self.width + margin, 0)
+impl<'c> Clip<'c> {
+    pub fn new(canvas: &'c dyn Canvas, pos: Point, size: Size) -> Clip<'c> {
+        Clip { canvas, pos, size }
     }
 
-    fn below_of(&self, margin: u32) -> Point {
-        self.pos + Point::new(0, self.height + margin)
+    // the part of a rectangle inside the clip, None if there is none
+    fn crop(&self, pos: Point, width: u32, height: u32) -> Option<(Point, u32, u32)> {
+        let left = pos.x.max(self.pos.x);
+        let top = pos.y.max(self.pos.y);
+        let right = (pos.x + width).min(self.width());
+        let bottom = (pos.y + height).min(self.height());
+        (left < right && top < bottom).then(|| (Point::new(left, top), right - left, bottom - top))
+    }
+
+    /* draws the part of a picture of `channels` bytes per pixel that is inside the clip */
+    fn picture(
+        &self,
+        pos: Point,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+        channels: usize,
+        draw: impl FnOnce(Point, u32, u32, &[u8]) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if pixels.len() < (width * height) as usize * channels {
+            return Err(Error::Render("picture smaller than its size"));
+        }
+        let Some((at, w, h)) = self.crop(pos, width, height) else {
+            return Ok(());
+        };
+        if (w, h) == (width, height) {
+            return draw(pos, width, height, pixels);
+        }
+
+        let (dx, dy) = ((at.x - pos.x) as usize, (at.y - pos.y) as usize);
+        let mut cropped = Vec::with_capacity((w * h) as usize * channels);
+        for y in dy..dy + h as usize {
+            let start = (y * width as usize + dx) * channels;
+            cropped.extend_from_slice(&pixels[start..start + w as usize * channels]);
+        }
+        draw(at, w, h, &cropped)
+    }
+}
+
+impl Canvas for Clip<'_> {
+    fn width(&self) -> u32 {
+        (self.pos.x + self.size.width).min(self.canvas.width())
+    }
+
+    fn height(&self) -> u32 {
+        (self.pos.y + self.size.height).min(self.canvas.height())
+    }
+
+    fn blend(&self, px: Point, color: Color, alpha: u8) -> Result<(), Error> {
+        match self.crop(px, 1, 1) {
+            Some(_) => self.canvas.blend(px, color, alpha),
+            None => Ok(()),
+        }
+    }
+
+    fn blit_gray(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        self.picture(pos, width, height, pixels, 1, |pos, w, h, pixels| {
+            self.canvas.blit_gray(pos, w, h, pixels)
+        })
+    }
+
+    fn composite(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        self.picture(pos, width, height, pixels, 2, |pos, w, h, pixels| {
+            self.canvas.composite(pos, w, h, pixels)
+        })
+    }
+
+    fn fill_rect(&self, pos: Point, width: u32, height: u32, color: Color) -> Result<(), Error> {
+        match self.crop(pos, width, height) {
+            Some((pos, w, h)) => self.canvas.fill_rect(pos, w, h, color),
+            None => Ok(()),
+        }
     }
 }
@@ -1,90 +1,676 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
-use crate::framebuffer::{self, Framebuffer, MxcfbRect};
+use crate::display::Display;
+use crate::dither::{dither, Dither};
+use crate::error::Error;
+use crate::framebuffer::{Framebuffer, Mode, Model, MxcfbRect};
 use crate::region::Region;
 use crate::util::{Color, Point};
 
-/* Abstraction over the framebuffer for our eink usecase */
-pub struct Screen<'a> {
-    pub(crate) fb: Rc<RefCell<Framebuffer>>,
+/* what regions and their objects need from a screen: its size and a way to plot pixels.
+ * this keeps regions independent of the display backend the screen is using.
+ */
+pub trait Canvas {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    /* paints `color` over the pixel at `px` with `alpha`, 255 replaces it */
+    fn blend(&self, px: Point, color: Color, alpha: u8) -> Result<(), Error>;
+    /* copies a grayscale picture of `width` x `height` pixels, row by row, to `pos` */
+    fn blit_gray(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error>;
+    /* like blit_gray, but `pixels` are gray and alpha pairs that are painted over what is there */
+    fn composite(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error>;
+    fn fill_rect(&self, pos: Point, width: u32, height: u32, color: Color) -> Result<(), Error>;
+}
+
+// full GC16 refresh after this many partial updates, unless configured otherwise
+pub const FULL_REFRESH_EVERY: u32 = 10;
+
+/* clockwise rotation of the picture on the display, e.g. 90 for a device that hangs on the wall
+ * turned 90 degrees counterclockwise
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u32) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::R0),
+            90 => Some(Rotation::R90),
+            180 => Some(Rotation::R180),
+            270 => Some(Rotation::R270),
+            _ => None,
+        }
+    }
+
+    // whether width and height of the screen are swapped relative to the display
+    fn swaps(&self) -> bool {
+        matches!(self, Rotation::R90 | Rotation::R270)
+    }
+}
+
+/* Abstraction over the display for our eink usecase.
+ * `width` and `height` are those of the rotated screen, everything drawn is rotated on its way
+ * into the frame, which has the orientation of the display.
+ * Drawing goes into an off-screen grayscale frame, the display is only written on update.
+ * `update` compares it with what the panel shows and only refreshes the rectangles that changed,
+ * which avoids the black-white flash of a full refresh.
+ * Partial updates leave ghosting behind though, so every so often the whole panel gets a full one.
+ */
+pub struct Screen<D: Display> {
+    pub(crate) fb: Rc<RefCell<D>>,
     pub width: u32,
     pub height: u32,
-    regions: Rc<RefCell<Vec<Region<'a>>>>,
+    rotation: Rotation,
+    // size of the display
+    fb_width: u32,
+    fb_height: u32,
+    regions: Rc<RefCell<Vec<Region>>>,
+    frame: RefCell<Vec<u8>>,
+    // the frame at the last update, None until there was one
+    shown: RefCell<Option<Vec<u8>>>,
+    // partial updates since the last full one
+    partial_updates: Cell<u32>,
+    // 0 makes every update a full one
+    full_refresh_every: Cell<u32>,
+    // mode of the partial updates
+    partial_mode: Cell<Mode>,
+    // how the frame is reduced to the gray levels of an update's mode
+    dither: Cell<Dither>,
 }
 
-impl<'a> Screen<'a> {
-    pub fn new() -> Option<Screen<'a>> {
-        let fb = Framebuffer::new("/dev/fb0");
-        let fb = match fb {
-            Ok(fb) => fb,
-            Err(_) => return None,
-        };
+/* bounding box of changed pixels on the display, right and bottom are inclusive */
+#[derive(Clone, Copy)]
+struct Dirty {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Dirty {
+    fn extend(dirty: &mut Option<Dirty>, x: u32, y: u32) {
+        *dirty = Some(match *dirty {
+            None => Dirty {
+                left: x,
+                top: y,
+                right: x,
+                bottom: y,
+            },
+            Some(d) => Dirty {
+                left: d.left.min(x),
+                top: d.top.min(y),
+                right: d.right.max(x),
+                bottom: d.bottom.max(y),
+            },
+        });
+    }
+
+    fn contains(&self, x: u32, y: u32) -> bool {
+        (self.left..=self.right).contains(&x) && (self.top..=self.bottom).contains(&y)
+    }
+}
 
-        let width = fb.var_info.xres;
-        let height = fb.var_info.yres;
-        let fb = Rc::new(RefCell::new(fb));
+impl From<Dirty> for MxcfbRect {
+    fn from(d: Dirty) -> Self {
+        MxcfbRect {
+            top: d.top,
+            left: d.left,
+            width: d.right - d.left + 1,
+            height: d.bottom - d.top + 1,
+        }
+    }
+}
 
-        Some(Screen::<'a> {
+impl Screen<Framebuffer> {
+    pub fn new(model: &'static Model, rotation: Rotation) -> Result<Screen<Framebuffer>, Error> {
+        let fb = Framebuffer::new("/dev/fb0", model).map_err(Error::Display)?;
+        Ok(Screen::with_display(fb, rotation))
+    }
+}
+
+impl<D: Display> Screen<D> {
+    pub fn with_display(display: D, rotation: Rotation) -> Screen<D> {
+        let fb_width = display.width();
+        let fb_height = display.height();
+        let (width, height) = if rotation.swaps() {
+            (fb_height, fb_width)
+        } else {
+            (fb_width, fb_height)
+        };
+        let fb = Rc::new(RefCell::new(display));
+
+        Screen::<D> {
             fb,
             width,
             height,
+            rotation,
+            fb_width,
+            fb_height,
             regions: Rc::new(RefCell::new(Vec::new())),
-        })
+            frame: RefCell::new(vec![255; (fb_width * fb_height) as usize]),
+            shown: RefCell::new(None),
+            partial_updates: Cell::new(0),
+            full_refresh_every: Cell::new(FULL_REFRESH_EVERY),
+            partial_mode: Cell::new(Mode::Partial),
+            dither: Cell::new(Dither::default()),
+        }
     }
 
-    pub fn add_region(&'a self, mut region: Region<'a>) {
-        region.screen = Some(&self);
+    pub fn set_full_refresh_every(&self, updates: u32) {
+        self.full_refresh_every.set(updates);
+    }
+
+    pub fn set_partial_mode(&self, mode: Mode) {
+        self.partial_mode.set(mode);
+    }
+
+    pub fn set_dither(&self, method: Dither) {
+        self.dither.set(method);
+    }
+
+    pub fn add_region(&self, region: Region) {
         let mut vector = self.regions.borrow_mut();
         vector.push(region);
     }
 
-    pub fn render(&self) {
+    // drops all regions, e.g. to build a new layout for the next refresh
+    pub fn remove_regions(&self) {
+        self.regions.borrow_mut().clear();
+    }
+
+    pub fn render(&self) -> Result<(), Error> {
         let regions = self.regions.borrow();
         for r in regions.iter() {
-            let _ = r.render();
+            r.render(self)?;
         }
+        Ok(())
     }
 
-    pub fn plot(&self, px: Point, color: Color) -> Result<(), &str> {
-        if px.x > self.width || px.y > self.height {
-            return Err("pixel outside of screen");
+    pub fn blend(&self, px: Point, color: Color, alpha: u8) -> Result<(), Error> {
+        if px.x >= self.width || px.y >= self.height {
+            return Err(Error::Render("pixel outside of screen"));
         }
 
-        let mut fb = self.fb.borrow_mut();
-        fb.set_pixel(px, [color.r, color.g, color.b]);
+        let i = self.index(px.x, px.y);
+        let mut frame = self.frame.borrow_mut();
+        frame[i] = color.over(Color::gray(frame[i]), alpha).luma();
+        Ok(())
+    }
+
+    pub fn blit_gray(
+        &self,
+        pos: Point,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), Error> {
+        if pos.x + width > self.width || pos.y + height > self.height {
+            return Err(Error::Render("picture outside of screen"));
+        }
+        if pixels.len() < (width * height) as usize {
+            return Err(Error::Render("picture smaller than its size"));
+        }
+
+        let mut frame = self.frame.borrow_mut();
+        for y in 0..height {
+            let src = &pixels[(y * width) as usize..((y + 1) * width) as usize];
+            if self.rotation == Rotation::R0 {
+                let start = ((pos.y + y) * self.fb_width + pos.x) as usize;
+                frame[start..start + width as usize].copy_from_slice(src);
+                continue;
+            }
+            for (x, g) in src.iter().enumerate() {
+                frame[self.index(pos.x + x as u32, pos.y + y)] = *g;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn composite(
+        &self,
+        pos: Point,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), Error> {
+        if pos.x + width > self.width || pos.y + height > self.height {
+            return Err(Error::Render("picture outside of screen"));
+        }
+        if pixels.len() < (width * height * 2) as usize {
+            return Err(Error::Render("picture smaller than its size"));
+        }
+
+        let mut frame = self.frame.borrow_mut();
+        for y in 0..height {
+            for x in 0..width {
+                let src = ((y * width + x) * 2) as usize;
+                let (gray, alpha) = (pixels[src], pixels[src + 1]);
+                let i = self.index(pos.x + x, pos.y + y);
+                frame[i] = Color::gray(gray).over(Color::gray(frame[i]), alpha).luma();
+            }
+        }
         Ok(())
     }
 
+    pub fn fill_rect(
+        &self,
+        pos: Point,
+        width: u32,
+        height: u32,
+        color: Color,
+    ) -> Result<(), Error> {
+        if pos.x + width > self.width || pos.y + height > self.height {
+            return Err(Error::Render("rectangle outside of screen"));
+        }
+
+        let gray = color.luma();
+        let mut frame = self.frame.borrow_mut();
+        for y in pos.y..pos.y + height {
+            if self.rotation == Rotation::R0 {
+                let start = (y * self.fb_width + pos.x) as usize;
+                frame[start..start + width as usize].fill(gray);
+                continue;
+            }
+            for x in pos.x..pos.x + width {
+                frame[self.index(x, y)] = gray;
+            }
+        }
+        Ok(())
+    }
+
+    // index of a point of the screen in the frame
+    fn index(&self, x: u32, y: u32) -> usize {
+        let (x, y) = self.to_display(x, y);
+        (y * self.fb_width + x) as usize
+    }
+
+    // where a point of the screen ends up on the display
+    fn to_display(&self, x: u32, y: u32) -> (u32, u32) {
+        match self.rotation {
+            Rotation::R0 => (x, y),
+            Rotation::R90 => (self.fb_width - 1 - y, x),
+            Rotation::R180 => (self.fb_width - 1 - x, self.fb_height - 1 - y),
+            Rotation::R270 => (y, self.fb_height - 1 - x),
+        }
+    }
+
+    // only whitens the frame, the panel isn't touched before the next update
     pub fn clear(&self) {
-        let mut fb = self.fb.borrow_mut();
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let _ = fb.set_pixel(Point::new(x, y), [255, 255, 255]);
+        self.frame.borrow_mut().fill(255);
+    }
+
+    /* bounding boxes of the pixels that differ from what the panel shows, one per region and one
+     * for everything outside of them, e.g. where a region of the last layout was
+     */
+    fn dirty_rects(&self, frame: &[u8], shown: &[u8]) -> Vec<Dirty> {
+        // regions that reach past the screen are clipped
+        let areas: Vec<Dirty> = self
+            .regions
+            .borrow()
+            .iter()
+            .map(|r| {
+                let right = (r.pos.x + r.width.max(1) - 1).min(self.width - 1);
+                let bottom = (r.pos.y + r.height.max(1) - 1).min(self.height - 1);
+                let (x0, y0) = self.to_display(r.pos.x.min(right), r.pos.y.min(bottom));
+                let (x1, y1) = self.to_display(right, bottom);
+                Dirty {
+                    left: x0.min(x1),
+                    top: y0.min(y1),
+                    right: x0.max(x1),
+                    bottom: y0.max(y1),
+                }
+            })
+            .collect();
+        let mut dirty: Vec<Option<Dirty>> = vec![None; areas.len() + 1];
+
+        let width = self.fb_width as usize;
+        for y in 0..self.fb_height {
+            let row = y as usize * width;
+            // most rows don't change, comparing them as a whole is a memcmp
+            if frame[row..row + width] == shown[row..row + width] {
+                continue;
+            }
+            for x in 0..self.fb_width {
+                let i = row + x as usize;
+                if frame[i] != shown[i] {
+                    let slot = areas
+                        .iter()
+                        .position(|a| a.contains(x, y))
+                        .unwrap_or(areas.len());
+                    Dirty::extend(&mut dirty[slot], x, y);
+                }
             }
         }
+        dirty.into_iter().flatten().collect()
     }
-    /* updates the screen with all previous drawing calls.
-     * blocks until screen is updated.
+
+    /* the pixels of `rect` of the frame, reduced to the gray levels `mode` can show. the frame
+     * itself keeps what was drawn, a full refresh shows more levels than a fast partial one
      */
-    pub fn update(&self) {
-        let mut fb = self.fb.borrow_mut();
-        let rect = MxcfbRect {
-            top: 0,
+    fn quantize(&self, frame: &[u8], rect: Dirty, mode: Mode) -> Vec<u8> {
+        let width = (rect.right - rect.left + 1) as usize;
+        let mut block = Vec::with_capacity(width * (rect.bottom - rect.top + 1) as usize);
+        for y in rect.top..=rect.bottom {
+            let start = (y * self.fb_width + rect.left) as usize;
+            block.extend_from_slice(&frame[start..start + width]);
+        }
+        let pos = Point::new(rect.left, rect.top);
+        dither(&mut block, width, pos, mode.levels(), self.dither.get());
+        block
+    }
+
+    /* sends everything drawn since the last update to the panel and blocks until it's visible.
+     * only the changed rectangles get a partial update, unless it's time for a full one.
+     */
+    pub fn update(&self) -> Result<(), Error> {
+        let frame = self.frame.borrow();
+        let mut shown = self.shown.borrow_mut();
+        let whole = Dirty {
             left: 0,
-            width: self.width,
-            height: self.height,
+            top: 0,
+            right: self.fb_width - 1,
+            bottom: self.fb_height - 1,
         };
 
-        let mode = framebuffer::Mode::Full;
-        match fb.update(rect, mode) {
-            Ok(_) => {}
-            Err(_) => todo!("Error handling screen update"),
+        let (rects, mode) = match shown.as_deref() {
+            Some(shown) if self.partial_updates.get() < self.full_refresh_every.get() => {
+                (self.dirty_rects(&frame, shown), self.partial_mode.get())
+            }
+            _ => (vec![whole], Mode::Full),
+        };
+        if rects.is_empty() {
+            return Ok(());
         }
 
-        match fb.wait() {
-            Ok(_) => {}
-            Err(_) => todo!("Error handling screen wait-for-update"),
+        let mut fb = self.fb.borrow_mut();
+        for rect in &rects {
+            let block = self.quantize(&frame, *rect, mode);
+            let width = (rect.right - rect.left + 1) as usize;
+            fb.blit_gray(&(*rect).into(), &block, width);
         }
+        match mode {
+            Mode::Full => {
+                fb.update_and_wait(whole.into(), mode)
+                    .map_err(Error::Display)?;
+                self.partial_updates.set(0);
+            }
+            _ => {
+                let mut markers = Vec::with_capacity(rects.len());
+                for rect in rects {
+                    markers.push(fb.update(rect.into(), mode).map_err(Error::Display)?);
+                }
+                fb.wait_all(&markers).map_err(Error::Display)?;
+                self.partial_updates.set(self.partial_updates.get() + 1);
+            }
+        }
+        *shown = Some(frame.clone());
+        Ok(())
+    }
+}
+
+impl<D: Display> Canvas for Screen<D> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn blend(&self, px: Point, color: Color, alpha: u8) -> Result<(), Error> {
+        Screen::blend(self, px, color, alpha)
+    }
+
+    fn blit_gray(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        Screen::blit_gray(self, pos, width, height, pixels)
+    }
+
+    fn composite(&self, pos: Point, width: u32, height: u32, pixels: &[u8]) -> Result<(), Error> {
+        Screen::composite(self, pos, width, height, pixels)
+    }
+
+    fn fill_rect(&self, pos: Point, width: u32, height: u32, color: Color) -> Result<(), Error> {
+        Screen::fill_rect(self, pos, width, height, color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::MemoryDisplay;
+
+    fn screen(width: u32, height: u32, rotation: Rotation) -> Screen<MemoryDisplay> {
+        Screen::with_display(MemoryDisplay::new(width, height), rotation)
+    }
+
+    fn gray(screen: &Screen<MemoryDisplay>, x: u32, y: u32) -> u8 {
+        let [r, g, b] = screen.fb.borrow().pixel(Point::new(x, y));
+        assert!(r == g && g == b);
+        r
+    }
+
+    #[test]
+    fn draws_into_the_display() {
+        let screen = screen(8, 6, Rotation::R0);
+        screen
+            .fill_rect(Point::new(2, 1), 3, 2, Color::gray(0))
+            .unwrap();
+        screen
+            .blit_gray(Point::new(0, 5), 4, 1, &[0x00, 0x11, 0x88, 0xff])
+            .unwrap();
+        // nothing reaches the display before the update, it is still black where the frame is
+        // white or gray
+        assert_eq!(gray(&screen, 0, 0), 0);
+        assert_eq!(gray(&screen, 2, 5), 0);
+        screen.update().unwrap();
+
+        for y in 0..5 {
+            for x in 0..8 {
+                let inside = (2..5).contains(&x) && (1..3).contains(&y);
+                assert_eq!(
+                    gray(&screen, x, y),
+                    if inside { 0 } else { 255 },
+                    "{},{}",
+                    x,
+                    y
+                );
+            }
+        }
+        let row: Vec<u8> = (0..4).map(|x| gray(&screen, x, 5)).collect();
+        assert_eq!(row, [0x00, 0x11, 0x88, 0xff]);
+    }
+
+    #[test]
+    fn updates_what_changed() {
+        let screen = screen(8, 6, Rotation::R0);
+        screen.update().unwrap();
+        assert_eq!(gray(&screen, 7, 5), 255);
+
+        screen.blend(Point::new(7, 5), Color::gray(0), 255).unwrap();
+        screen.update().unwrap();
+        assert_eq!(gray(&screen, 7, 5), 0);
+        assert_eq!(gray(&screen, 6, 5), 255);
+        assert_eq!(
+            updates(&screen).last(),
+            Some(&((7, 5, 1, 1), Mode::Partial))
+        );
+
+        screen.clear();
+        screen.update().unwrap();
+        assert_eq!(gray(&screen, 7, 5), 255);
+    }
+
+    // (left, top, width, height) and mode of the updates since the last call
+    fn updates(screen: &Screen<MemoryDisplay>) -> Vec<((u32, u32, u32, u32), Mode)> {
+        let mut fb = screen.fb.borrow_mut();
+        fb.updates
+            .drain(..)
+            .map(|(r, mode)| ((r.left, r.top, r.width, r.height), mode))
+            .collect()
+    }
+
+    #[test]
+    fn updates_changed_regions_apart() {
+        let screen = screen(20, 20, Rotation::R0);
+        screen.add_region(Region::new(Point::new(0, 0), 10, 10, false));
+        screen.add_region(Region::new(Point::new(10, 10), 10, 10, false));
+        screen.set_full_refresh_every(3);
+        screen.update().unwrap();
+        assert_eq!(updates(&screen), [((0, 0, 20, 20), Mode::Full)]);
+
+        // only the pixels that changed in the first region
+        screen
+            .fill_rect(Point::new(2, 3), 2, 1, Color::gray(0))
+            .unwrap();
+        screen
+            .fill_rect(Point::new(15, 2), 1, 1, Color::gray(0))
+            .unwrap();
+        screen.update().unwrap();
+        assert_eq!(
+            updates(&screen),
+            [
+                ((2, 3, 2, 1), Mode::Partial),
+                ((15, 2, 1, 1), Mode::Partial)
+            ]
+        );
+
+        // what is gone outside of the regions is refreshed apart from the second region
+        screen.clear();
+        screen
+            .blend(Point::new(12, 14), Color::gray(0), 255)
+            .unwrap();
+        screen.update().unwrap();
+        assert_eq!(
+            updates(&screen),
+            [
+                ((2, 3, 2, 1), Mode::Partial),
+                ((12, 14, 1, 1), Mode::Partial),
+                ((15, 2, 1, 1), Mode::Partial)
+            ]
+        );
+
+        // nothing changed, nothing to update
+        screen.update().unwrap();
+        assert!(updates(&screen).is_empty());
+
+        // the third partial update is the last one before a full refresh of the whole panel
+        screen.blend(Point::new(0, 0), Color::gray(0), 255).unwrap();
+        screen.update().unwrap();
+        assert_eq!(updates(&screen), [((0, 0, 1, 1), Mode::Partial)]);
+        screen.blend(Point::new(1, 0), Color::gray(0), 255).unwrap();
+        screen.update().unwrap();
+        assert_eq!(updates(&screen), [((0, 0, 20, 20), Mode::Full)]);
+    }
+
+    #[test]
+    fn rotates_into_the_display() {
+        // where the top left corner and the pixel right of it end up on a 6x8 display
+        let cases = [
+            (Rotation::R0, (0, 0), (1, 0)),
+            (Rotation::R90, (5, 0), (5, 1)),
+            (Rotation::R180, (5, 7), (4, 7)),
+            (Rotation::R270, (0, 7), (0, 6)),
+        ];
+        for (rotation, corner, next) in cases {
+            let screen = screen(6, 8, rotation);
+            let size = if rotation.swaps() { (8, 6) } else { (6, 8) };
+            assert_eq!((screen.width, screen.height), size);
+
+            screen.blend(Point::new(0, 0), Color::gray(0), 255).unwrap();
+            screen
+                .fill_rect(Point::new(1, 0), 1, 1, Color::gray(0x88))
+                .unwrap();
+            screen.update().unwrap();
+            assert_eq!(gray(&screen, corner.0, corner.1), 0, "{:?}", rotation);
+            assert_eq!(gray(&screen, next.0, next.1), 0x88, "{:?}", rotation);
+        }
+    }
+
+    fn frame_values(screen: &Screen<MemoryDisplay>) -> Vec<u8> {
+        screen.update().unwrap();
+        let mut values = Vec::new();
+        for y in 0..screen.height {
+            for x in 0..screen.width {
+                values.push(gray(screen, x, y));
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn quantizes_fills_and_blends() {
+        for (mode, allowed) in [
+            (Mode::Fast, &[0, 255][..]),
+            (Mode::Gray4, &[0, 85, 170, 255][..]),
+        ] {
+            for method in [Dither::None, Dither::FloydSteinberg, Dither::Ordered] {
+                let screen = screen(16, 16, Rotation::R90);
+                screen.set_partial_mode(mode);
+                screen.set_dither(method);
+                // the first update is a full one, the next is the partial one
+                screen.update().unwrap();
+                screen
+                    .fill_rect(Point::new(0, 0), 16, 8, Color::gray(0x80))
+                    .unwrap();
+                for x in 0..16 {
+                    for y in 8..16 {
+                        screen.blend(Point::new(x, y), Color::gray(0), 100).unwrap();
+                    }
+                }
+                let values = frame_values(&screen);
+                for v in &values {
+                    assert!(allowed.contains(v), "{} in {:?} {:?}", v, mode, method);
+                }
+                if method == Dither::Ordered {
+                    // a dithered area keeps its brightness on average
+                    let mean = values[..128].iter().map(|v| *v as u32).sum::<u32>() / 128;
+                    assert!((100..156).contains(&mean), "{} in {:?}", mean, mode);
+                }
+            }
+        }
+
+        // levels the mode can show are left alone
+        let screen = screen(4, 4, Rotation::R0);
+        screen.set_partial_mode(Mode::Gray4);
+        screen.update().unwrap();
+        screen
+            .fill_rect(Point::new(0, 0), 4, 4, Color::gray(170))
+            .unwrap();
+        assert!(frame_values(&screen).iter().all(|v| *v == 170));
+    }
+
+    #[test]
+    fn full_refresh_shows_more_levels() {
+        let screen = screen(4, 4, Rotation::R0);
+        screen.set_partial_mode(Mode::Fast);
+        screen.set_dither(Dither::None);
+        screen.set_full_refresh_every(1);
+        screen.update().unwrap();
+        screen
+            .fill_rect(Point::new(0, 0), 4, 4, Color::gray(0x88))
+            .unwrap();
+        assert!(frame_values(&screen).iter().all(|v| *v == 0xff));
+
+        // the frame keeps what was drawn, so the full refresh brings back the gray
+        screen
+            .fill_rect(Point::new(0, 0), 1, 1, Color::gray(0))
+            .unwrap();
+        assert!(frame_values(&screen)[1..].iter().all(|v| *v == 0x88));
+    }
+
+    #[test]
+    fn refuses_drawing_outside() {
+        let screen = screen(8, 6, Rotation::R90);
+        assert!(screen.blend(Point::new(6, 0), Color::gray(0), 255).is_err());
+        assert!(screen
+            .fill_rect(Point::new(4, 4), 4, 3, Color::gray(0))
+            .is_err());
+        assert!(screen.blit_gray(Point::new(0, 0), 2, 2, &[0; 3]).is_err());
     }
 }
@@ -1,4 +1,13 @@
-#[derive(Copy, Clone)]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// seconds since the epoch, 0 if the clock is before 1970 which happens on a Kobo without RTC sync
+pub fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Point {
     pub x: u32,
     pub y: u32,
@@ -21,6 +30,7 @@ impl std::ops::Add for Point {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -31,10 +41,73 @@ impl Color {
     pub fn new(r: u8, g: u8, b: u8) -> Color {
         Color { r, g, b }
     }
+
+    pub fn gray(v: u8) -> Color {
+        Color { r: v, g: v, b: v }
+    }
+
+    /* `self` painted over `below` with `alpha`, from 0 (not at all) to 255 (covers it). alpha is
+     * the opacity of an image pixel or the coverage of an antialiased glyph pixel alike.
+     */
+    pub fn over(&self, below: Color, alpha: u8) -> Color {
+        Color {
+            r: mix(self.r, below.r, alpha),
+            g: mix(self.g, below.g, alpha),
+            b: mix(self.b, below.b, alpha),
+        }
+    }
+
+    pub fn luma(&self) -> u8 {
+        luma([self.r, self.g, self.b])
+    }
+}
+
+// src * alpha + dst * (1 - alpha) with alpha in 0..=255, rounded
+fn mix(src: u8, dst: u8, alpha: u8) -> u8 {
+    let alpha = alpha as u32;
+    ((src as u32 * alpha + dst as u32 * (255 - alpha) + 127) / 255) as u8
+}
+
+// ITU-R BT.601 luma, what grayscale panels show for a color
+pub fn luma(rgb: [u8; 3]) -> u8 {
+    ((rgb[0] as u32 * 299 + rgb[1] as u32 * 587 + rgb[2] as u32 * 114) / 1000) as u8
 }
 
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct FontSetting {
-    pub name: &'static str,
+    pub name: String,
     pub size: f32,
+    #[serde(default = "full_saturation")]
     pub saturation: f32,
 }
+
+fn full_saturation() -> f32 {
+    1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixes_by_alpha() {
+        let ink = Color::new(0, 100, 255);
+        let paper = Color::new(255, 200, 0);
+        assert_eq!(ink.over(paper, 0), paper);
+        assert_eq!(ink.over(paper, 255), ink);
+        // halfway, rounded to the nearest
+        assert_eq!(ink.over(paper, 128), Color::new(127, 150, 128));
+        assert_eq!(mix(0, 255, 127), 128);
+        assert_eq!(mix(255, 0, 1), 1);
+        assert_eq!(mix(255, 0, 254), 254);
+    }
+
+    #[test]
+    fn takes_luma_of_colors() {
+        assert_eq!(Color::gray(0x88).luma(), 0x88);
+        assert_eq!(Color::new(255, 255, 255).luma(), 255);
+        // green is the brightest to the eye
+        assert!(Color::new(0, 255, 0).luma() > Color::new(255, 0, 0).luma());
+        assert!(Color::new(255, 0, 0).luma() > Color::new(0, 0, 255).luma());
+    }
+}